@@ -0,0 +1,74 @@
+//! A backtracking solver.
+//!
+//! This makes no attempt to explain itself.  It guesses at the cell with
+//! the fewest possibilities, lets `SGrid::set_cell` remove the guess from
+//! every cell it sees, and backs out of any guess which leaves a cell
+//! with no possibilities.  It will find a solution to any grid which has
+//! one, which makes it a useful last resort and a way to check answers.
+
+use super::grid::{SCell, SGrid};
+use super::types::SResult;
+
+/// Find a solution to the grid, if there is one.  If the grid has more
+/// than one solution, the first one found is returned.
+pub fn backtrack(grid: &SGrid) -> Option<SGrid> {
+    backtrack_with(grid, |_| {})
+}
+
+/// Find a solution to the grid, trying the values for each guess in the
+/// order `order` leaves them in.  Shuffling them gives a random solution.
+pub fn backtrack_with<F>(grid: &SGrid, mut order: F) -> Option<SGrid>
+where
+    F: FnMut(&mut Vec<u8>),
+{
+    let mut grid = grid.clone();
+    if search(&mut grid, &mut order) {
+        Some(grid)
+    } else {
+        None
+    }
+}
+
+/// Fill in the grid, rolling back any guess which does not lead to a
+/// solution.  Returns false, with the grid as it was, if there is none.
+fn search<F>(grid: &mut SGrid, order: &mut F) -> bool
+where
+    F: FnMut(&mut Vec<u8>),
+{
+    let mut best: Option<(usize, usize, SCell)> = None;
+    for row in 0..9 {
+        for col in 0..9 {
+            let cell = grid.cell(row, col);
+            match cell.possibilities() {
+                0 if matches!(cell, SCell::Possible(_)) => return false,
+                0 => {}
+                n => {
+                    if best.map(|(_, _, b)| n < b.possibilities()).unwrap_or(true) {
+                        best = Some((row, col, cell));
+                    }
+                }
+            }
+        }
+    }
+    let (row, col, cell) = match best {
+        Some(best) => best,
+        None => return true,
+    };
+    let mut values = cell.values().collect::<Vec<_>>();
+    order(&mut values);
+    let checkpoint = grid.checkpoint();
+    let mut found = false;
+    for value in values {
+        found = match grid.set_cell(row, col, value) {
+            SResult::Finished => true,
+            SResult::Continue => search(grid, order),
+            _ => false,
+        };
+        if found {
+            break;
+        }
+        grid.rollback(checkpoint);
+    }
+    grid.release(checkpoint);
+    found
+}
@@ -0,0 +1,408 @@
+//! Alternating inference chains.
+//!
+//! A chain is built over nodes, each of which is the claim that a value
+//! is in (at least one of) a small set of cells.  Nodes are joined by
+//! links.  A strong link says that if one node is false then the other
+//! must be true, and a weak link says that if one node is true then the
+//! other must be false.  Following links alternately from a node which is
+//! assumed false gives a chain of implications whose ends cannot both be
+//! false.
+//!
+//! Weak links come from the ruleset's idea of which cells see which, so
+//! variant constraints add links without the engine knowing about them.
+
+use super::grid::{SCell, SGrid};
+
+use std::collections::{HashMap, VecDeque};
+
+/// A node in the chain graph.  Usually this is a single cell, but when
+/// grouped nodes are enabled it may be several cells in the intersection
+/// of a box and a line, in which case it claims that at least one of
+/// those cells is the value.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub value: u8,
+    pub cells: Vec<(usize, usize)>,
+}
+
+impl Node {
+    fn single(row: usize, col: usize, value: u8) -> Self {
+        Self {
+            value,
+            cells: vec![(row, col)],
+        }
+    }
+}
+
+/// Which kinds of link a graph is built from.
+#[derive(Copy, Clone)]
+pub struct LinkKinds {
+    /// Restrict the graph to a single value
+    pub value: Option<u8>,
+    /// Strong links between the two values of a bivalue cell
+    pub bivalue: bool,
+    /// Strong links between the only two places for a value in a house
+    pub conjugate: bool,
+    /// Grouped nodes in box/line intersections
+    pub groups: bool,
+    /// Weak links between values in the same cell
+    pub cell_weak: bool,
+}
+
+impl LinkKinds {
+    /// Everything, as used for general alternating inference chains
+    pub const ALL: LinkKinds = LinkKinds {
+        value: None,
+        bivalue: true,
+        conjugate: true,
+        groups: true,
+        cell_weak: true,
+    };
+
+    /// Conjugate pairs for a single value, as used by X-Chains
+    pub fn single_value(value: u8) -> Self {
+        Self {
+            value: Some(value),
+            bivalue: false,
+            conjugate: true,
+            groups: false,
+            cell_weak: false,
+        }
+    }
+
+    /// Bivalue cells linked by the values they share, as used by XY-Chains
+    pub const BIVALUE: LinkKinds = LinkKinds {
+        value: None,
+        bivalue: true,
+        conjugate: false,
+        groups: false,
+        cell_weak: false,
+    };
+}
+
+/// What a chain allows us to conclude
+#[derive(Debug)]
+pub enum Conclusion {
+    /// These candidates (row, col, value) can be removed
+    Eliminate(Vec<(usize, usize, u8)>),
+    /// This value can be placed in this cell
+    Place(usize, usize, u8),
+}
+
+/// A chain found in the graph.  The nodes alternate between being false
+/// and true, starting with the first node false and linked to the second
+/// by a strong link.
+#[derive(Debug)]
+pub struct Chain {
+    pub nodes: Vec<Node>,
+    pub conclusion: Conclusion,
+}
+
+/// A set of candidates, indexed by `target_index`
+#[derive(Clone)]
+struct Targets([u64; 12]);
+
+fn target_index(row: usize, col: usize, value: u8) -> usize {
+    (row * 9 + col) * 9 + (value as usize - 1)
+}
+
+impl Targets {
+    fn new() -> Self {
+        Targets([0; 12])
+    }
+
+    fn insert(&mut self, idx: usize) {
+        self.0[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn contains(&self, idx: usize) -> bool {
+        (self.0[idx / 64] & (1 << (idx % 64))) != 0
+    }
+
+    fn intersect(&self, other: &Targets) -> Targets {
+        let mut ret = self.clone();
+        ret.0
+            .iter_mut()
+            .zip(other.0.iter())
+            .for_each(|(a, b)| *a &= b);
+        ret
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        (0..729)
+            .filter(move |&idx| self.contains(idx))
+            .map(|idx| (idx / 81, (idx / 9) % 9, (idx % 9) as u8 + 1))
+    }
+}
+
+/// A state in a walk of the graph is a node and whether it is true.  Each
+/// reached state maps to the state it was reached from.
+type Parents = HashMap<(usize, bool), (usize, bool)>;
+
+/// The graph of nodes and links for a grid
+pub struct LinkGraph {
+    nodes: Vec<Node>,
+    strong: Vec<Vec<usize>>,
+    weak: Vec<Vec<usize>>,
+    /// For each node, every candidate which is false if the node is true
+    falsifies: Vec<Targets>,
+}
+
+fn is_candidate(grid: &SGrid, row: usize, col: usize, value: u8) -> bool {
+    matches!(grid.cell(row, col), SCell::Possible(_)) && grid.cell(row, col).has(value)
+}
+
+impl LinkGraph {
+    pub fn new(grid: &SGrid, kinds: LinkKinds) -> Self {
+        let rules = grid.rules();
+        let wanted = |value: u8| kinds.value.map(|v| v == value).unwrap_or(true);
+        let mut ret = LinkGraph {
+            nodes: Vec::new(),
+            strong: Vec::new(),
+            weak: Vec::new(),
+            falsifies: Vec::new(),
+        };
+        let mut index = HashMap::new();
+        let mut add_node = |ret: &mut LinkGraph, node: Node| -> usize {
+            *index.entry(node.clone()).or_insert_with(|| {
+                let mut falsifies = Targets::new();
+                let (row, col) = node.cells[0];
+                for &(orow, ocol) in rules.sees(row, col) {
+                    if node
+                        .cells
+                        .iter()
+                        .all(|&(r, c)| rules.sees(r, c).contains(&(orow, ocol)))
+                    {
+                        falsifies.insert(target_index(orow, ocol, node.value));
+                    }
+                }
+                if node.cells.len() == 1 {
+                    for value in (1..=9).filter(|&v| v != node.value) {
+                        falsifies.insert(target_index(row, col, value));
+                    }
+                }
+                ret.nodes.push(node);
+                ret.strong.push(Vec::new());
+                ret.weak.push(Vec::new());
+                ret.falsifies.push(falsifies);
+                ret.nodes.len() - 1
+            })
+        };
+        let add_strong = |ret: &mut LinkGraph, a: usize, b: usize| {
+            if !ret.strong[a].contains(&b) {
+                ret.strong[a].push(b);
+                ret.strong[b].push(a);
+            }
+        };
+
+        // Every candidate is a node
+        for row in 0..9 {
+            for col in 0..9 {
+                for value in grid.cell(row, col).values() {
+                    if wanted(value) && is_candidate(grid, row, col, value) {
+                        add_node(&mut ret, Node::single(row, col, value));
+                    }
+                }
+            }
+        }
+
+        if kinds.bivalue {
+            for row in 0..9 {
+                for col in 0..9 {
+                    let cell = grid.cell(row, col);
+                    if cell.possibilities() != 2 {
+                        continue;
+                    }
+                    let mut values = cell.values().filter(|&v| wanted(v));
+                    if let (Some(a), Some(b)) = (values.next(), values.next()) {
+                        let a = add_node(&mut ret, Node::single(row, col, a));
+                        let b = add_node(&mut ret, Node::single(row, col, b));
+                        add_strong(&mut ret, a, b);
+                    }
+                }
+            }
+        }
+
+        if kinds.conjugate {
+            for house in 0..rules.houses().len() {
+                for value in (1..=9).filter(|&v| wanted(v)) {
+                    let found = rules.houses()[house]
+                        .iter()
+                        .copied()
+                        .filter(|&(row, col)| is_candidate(grid, row, col, value))
+                        .collect::<Vec<_>>();
+                    let mut splits = Vec::new();
+                    if found.len() == 2 {
+                        splits.push((vec![found[0]], vec![found[1]]));
+                    }
+                    if kinds.groups && found.len() > 2 {
+                        // Split the house's cells into a group in one
+                        // overlapping house, and the rest.  The rest must
+                        // itself be a single cell or a group.
+                        let overlaps = rules
+                            .overlapping_houses(house)
+                            .iter()
+                            .map(|&other| rules.houses()[other].to_vec())
+                            .collect::<Vec<_>>();
+                        for (n, overlap) in overlaps.iter().enumerate() {
+                            let (group, rest): (Vec<_>, Vec<_>) =
+                                found.iter().partition(|pos| overlap.contains(pos));
+                            if group.is_empty() || rest.is_empty() {
+                                continue;
+                            }
+                            if rest.len() == 1
+                                || overlaps.iter().enumerate().any(|(m, other)| {
+                                    m != n && rest.iter().all(|pos| other.contains(pos))
+                                })
+                            {
+                                splits.push((group, rest));
+                            }
+                        }
+                    }
+                    for (a, b) in splits {
+                        let a = add_node(&mut ret, Node { value, cells: a });
+                        let b = add_node(&mut ret, Node { value, cells: b });
+                        add_strong(&mut ret, a, b);
+                    }
+                }
+            }
+        }
+
+        // Weak links between nodes follow from what each node falsifies
+        for a in 0..ret.nodes.len() {
+            for b in 0..ret.nodes.len() {
+                let (na, nb) = (&ret.nodes[a], &ret.nodes[b]);
+                let linked = if na.value == nb.value {
+                    nb.cells.iter().all(|&(row, col)| {
+                        ret.falsifies[a].contains(target_index(row, col, nb.value))
+                    })
+                } else {
+                    kinds.cell_weak
+                        && na.cells.len() == 1
+                        && nb.cells.len() == 1
+                        && na.cells[0] == nb.cells[0]
+                };
+                if linked {
+                    ret.weak[a].push(b);
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Walk the graph from a starting state, alternating strong and weak
+    /// links.  Returns the parent of each reached state, from which the
+    /// chains can be rebuilt, along with the states in the order they
+    /// were reached.
+    fn walk(&self, start: usize, truth: bool) -> (Parents, Vec<(usize, bool)>) {
+        let mut parents = HashMap::new();
+        let mut order = Vec::new();
+        let mut queue = VecDeque::new();
+        let root = (start, truth);
+        queue.push_back(root);
+        while let Some((node, truth)) = queue.pop_front() {
+            // A false node forces its strong partners true, and a true
+            // node forces its weak partners false.
+            let next = if truth {
+                &self.weak[node]
+            } else {
+                &self.strong[node]
+            };
+            for &other in next {
+                let state = (other, !truth);
+                if state == root || parents.contains_key(&state) {
+                    continue;
+                }
+                parents.insert(state, (node, truth));
+                order.push(state);
+                queue.push_back(state);
+            }
+        }
+        (parents, order)
+    }
+
+    fn rebuild(&self, parents: &Parents, end: (usize, bool)) -> Vec<Node> {
+        let mut ret = vec![self.nodes[end.0].clone()];
+        let mut state = end;
+        while let Some(&parent) = parents.get(&state) {
+            ret.push(self.nodes[parent.0].clone());
+            state = parent;
+        }
+        ret.reverse();
+        ret
+    }
+
+    /// Find the shortest alternating inference chain which allows some
+    /// candidate to be eliminated.  The chain starts with a node which,
+    /// if false, forces the end node true; so one of the two is true and
+    /// any candidate falsified by both can be removed.
+    pub fn find_chain(&self, grid: &SGrid) -> Option<Chain> {
+        let mut best: Option<Chain> = None;
+        for start in 0..self.nodes.len() {
+            let (parents, order) = self.walk(start, false);
+            for &(end, truth) in &order {
+                if !truth || end == start {
+                    continue;
+                }
+                let eliminations = self.falsifies[start]
+                    .intersect(&self.falsifies[end])
+                    .iter()
+                    .filter(|&(row, col, value)| is_candidate(grid, row, col, value))
+                    .collect::<Vec<_>>();
+                if eliminations.is_empty() {
+                    continue;
+                }
+                let nodes = self.rebuild(&parents, (end, truth));
+                if best
+                    .as_ref()
+                    .map(|b| nodes.len() < b.nodes.len())
+                    .unwrap_or(true)
+                {
+                    best = Some(Chain {
+                        nodes,
+                        conclusion: Conclusion::Eliminate(eliminations),
+                    });
+                }
+                // Later ends from this start are never shorter
+                break;
+            }
+        }
+        best
+    }
+
+    /// Find the shortest discontinuous nice loop.  Assuming a candidate is
+    /// true and following the links back to the candidate being false
+    /// means it can be removed; assuming it false and arriving back at it
+    /// being true means it can be placed.
+    pub fn find_loop(&self) -> Option<Chain> {
+        let mut best: Option<Chain> = None;
+        for start in (0..self.nodes.len()).filter(|&n| self.nodes[n].cells.len() == 1) {
+            for &truth in &[true, false] {
+                let (parents, _) = self.walk(start, truth);
+                let end = (start, !truth);
+                if !parents.contains_key(&end) {
+                    continue;
+                }
+                let nodes = self.rebuild(&parents, end);
+                if best
+                    .as_ref()
+                    .map(|b| nodes.len() < b.nodes.len())
+                    .unwrap_or(true)
+                {
+                    let (row, col) = self.nodes[start].cells[0];
+                    let value = self.nodes[start].value;
+                    best = Some(Chain {
+                        nodes,
+                        conclusion: if truth {
+                            Conclusion::Eliminate(vec![(row, col, value)])
+                        } else {
+                            Conclusion::Place(row, col, value)
+                        },
+                    });
+                }
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,272 @@
+//! An exact cover solver using Knuth's dancing links (Algorithm X).
+//!
+//! Every way of putting a value in a cell is a row of the matrix.  The
+//! primary columns are the constraints which must be met exactly once:
+//! each cell has one value, and each house has each value once.  Any other
+//! pair of cells which the ruleset says see each other gets secondary
+//! columns, one per value, which may be met at most once; so variant
+//! rulesets are honoured without the solver knowing about them.
+//!
+//! This is much faster than the backtracking solver, which makes it the
+//! tool for counting solutions and checking that a puzzle is unique.
+
+use super::grid::SGrid;
+
+use std::collections::HashMap;
+
+/// The dancing links matrix.  Node 0 is the root, nodes 1 to `columns`
+/// are the column headers, and the rest are the ones in the matrix.
+struct Dlx {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl Dlx {
+    /// Create a matrix with the given number of primary columns, followed
+    /// by the given number of secondary columns.
+    fn new(primary: usize, secondary: usize) -> Self {
+        let columns = primary + secondary;
+        let mut ret = Dlx {
+            left: Vec::new(),
+            right: Vec::new(),
+            up: Vec::new(),
+            down: Vec::new(),
+            column: Vec::new(),
+            size: vec![0; columns + 1],
+        };
+        for node in 0..=columns {
+            // Primary headers (and the root) form a ring; secondary
+            // headers are left out of it, linked only to themselves.
+            let (left, right) = if node == 0 {
+                (primary, if primary == 0 { 0 } else { 1 })
+            } else if node <= primary {
+                (node - 1, if node == primary { 0 } else { node + 1 })
+            } else {
+                (node, node)
+            };
+            ret.left.push(left);
+            ret.right.push(right);
+            ret.up.push(node);
+            ret.down.push(node);
+            ret.column.push(node);
+        }
+        ret
+    }
+
+    /// Add a row which covers the given columns (numbered from zero)
+    fn add_row(&mut self, columns: &[usize]) {
+        let first = self.left.len();
+        for (n, &column) in columns.iter().enumerate() {
+            let header = column + 1;
+            let node = self.left.len();
+            self.left.push(if n == 0 { node } else { node - 1 });
+            self.right.push(first);
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            self.column.push(header);
+            let above = self.up[header];
+            self.down[above] = node;
+            self.up[header] = node;
+            self.size[header] += 1;
+            if n != 0 {
+                self.right[node - 1] = node;
+                self.left[first] = node;
+            }
+        }
+    }
+
+    fn cover(&mut self, header: usize) {
+        let (left, right) = (self.left[header], self.right[header]);
+        self.right[left] = right;
+        self.left[right] = left;
+        let mut row = self.down[header];
+        while row != header {
+            let mut node = self.right[row];
+            while node != row {
+                let (up, down) = (self.up[node], self.down[node]);
+                self.down[up] = down;
+                self.up[down] = up;
+                self.size[self.column[node]] -= 1;
+                node = self.right[node];
+            }
+            row = self.down[row];
+        }
+    }
+
+    fn uncover(&mut self, header: usize) {
+        let mut row = self.up[header];
+        while row != header {
+            let mut node = self.left[row];
+            while node != row {
+                let (up, down) = (self.up[node], self.down[node]);
+                self.down[up] = node;
+                self.up[down] = node;
+                self.size[self.column[node]] += 1;
+                node = self.left[node];
+            }
+            row = self.up[row];
+        }
+        let (left, right) = (self.left[header], self.right[header]);
+        self.right[left] = header;
+        self.left[right] = header;
+    }
+
+    /// Search for exact covers, stopping once `limit` have been found
+    fn search(&mut self, limit: usize, count: &mut usize) {
+        if self.right[0] == 0 {
+            *count += 1;
+            return;
+        }
+        // Choose the column with the fewest rows
+        let mut header = self.right[0];
+        let mut best = header;
+        while header != 0 {
+            if self.size[header] < self.size[best] {
+                best = header;
+            }
+            header = self.right[header];
+        }
+        if self.size[best] == 0 {
+            return;
+        }
+        self.cover(best);
+        let mut row = self.down[best];
+        while row != best && *count < limit {
+            let mut node = self.right[row];
+            while node != row {
+                self.cover(self.column[node]);
+                node = self.right[node];
+            }
+            self.search(limit, count);
+            let mut node = self.left[row];
+            while node != row {
+                self.uncover(self.column[node]);
+                node = self.left[node];
+            }
+            row = self.down[row];
+        }
+        self.uncover(best);
+    }
+}
+
+/// Build the exact cover matrix for a grid, with a row for each value
+/// which is still possible in each cell.
+fn build(grid: &SGrid) -> Dlx {
+    let rules = grid.rules();
+    let mut houses_of = vec![Vec::new(); 81];
+    let houses = rules.houses().len();
+    for (house, cells) in rules.houses().iter().enumerate() {
+        for &(row, col) in cells {
+            houses_of[row * 9 + col].push(house);
+        }
+    }
+    // Pairs of cells which see each other without sharing a house
+    let mut pairs = HashMap::new();
+    for row in 0..9 {
+        for col in 0..9 {
+            for &(orow, ocol) in rules.sees(row, col) {
+                let (a, b) = (row * 9 + col, orow * 9 + ocol);
+                if a < b && !houses_of[a].iter().any(|h| houses_of[b].contains(h)) {
+                    let next = pairs.len();
+                    pairs.insert((a, b), next);
+                }
+            }
+        }
+    }
+    let mut peers_of = vec![Vec::new(); 81];
+    for (&(a, b), &n) in &pairs {
+        peers_of[a].push(n);
+        peers_of[b].push(n);
+    }
+
+    // Primary columns: 81 cells, then each house by 9 values
+    let primary = 81 + houses * 9;
+    let mut dlx = Dlx::new(primary, pairs.len() * 9);
+    for row in 0..9 {
+        for col in 0..9 {
+            let pos = row * 9 + col;
+            for value in grid.cell(row, col).values() {
+                let mut columns = vec![pos];
+                columns.extend(
+                    houses_of[pos]
+                        .iter()
+                        .map(|house| 81 + house * 9 + (value as usize - 1)),
+                );
+                columns.extend(
+                    peers_of[pos]
+                        .iter()
+                        .map(|pair| primary + pair * 9 + (value as usize - 1)),
+                );
+                dlx.add_row(&columns);
+            }
+        }
+    }
+    dlx
+}
+
+/// Count the solutions to the grid, stopping once `limit` are found.
+pub fn count_solutions(grid: &SGrid, limit: usize) -> usize {
+    let mut count = 0;
+    build(grid).search(limit, &mut count);
+    count
+}
+
+/// Whether the grid has exactly one solution
+pub fn is_unique(grid: &SGrid) -> bool {
+    count_solutions(grid, 2) == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{AntiKnight, Normal, Ruleset, XSudoku};
+    use crate::test_grid;
+
+    const UNIQUE: &str =
+        "...26.7.168..7..9.19...45..82.1...4...46.29...5...3.28..93...74.4..5..367.3.18...";
+    // UNIQUE with a wrong 3 in r2c7, which set_cell does not catch
+    const NO_SOLUTION: &str =
+        "...26.7.168..7.39.19...45..82.1...4...46.29...5...3.28..93...74.4..5..367.3.18...";
+    // Unique as an anti-knight puzzle, but not as a normal one
+    const ANTI_KNIGHT: &str =
+        "..5................6......31......9.479...268.2......52......5................3..";
+    // Unique as an X-Sudoku puzzle, but not as a normal one
+    const X_SUDOKU: &str =
+        "......35...9..............7.6...1..2..42.86..9..4...1.6..............9...83......";
+
+    #[test]
+    fn counts_solutions() {
+        assert_eq!(
+            count_solutions(&test_grid(Normal::new(), NO_SOLUTION), 10),
+            0
+        );
+        assert_eq!(count_solutions(&test_grid(Normal::new(), UNIQUE), 10), 1);
+        assert!(is_unique(&test_grid(Normal::new(), UNIQUE)));
+        assert!(!is_unique(&test_grid(Normal::new(), NO_SOLUTION)));
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let blank = SGrid::new(Normal::new());
+        assert_eq!(count_solutions(&blank, 1), 1);
+        assert_eq!(count_solutions(&blank, 50), 50);
+        assert!(!is_unique(&blank));
+    }
+
+    #[test]
+    fn honours_variant_rules() {
+        assert_eq!(
+            count_solutions(&test_grid(Normal::new(), ANTI_KNIGHT), 2),
+            2
+        );
+        let rules = Normal::new().with(&AntiKnight);
+        assert!(is_unique(&test_grid(rules, ANTI_KNIGHT)));
+
+        assert_eq!(count_solutions(&test_grid(Normal::new(), X_SUDOKU), 2), 2);
+        assert!(is_unique(&test_grid(XSudoku::new(), X_SUDOKU)));
+    }
+}
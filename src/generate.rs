@@ -0,0 +1,291 @@
+//! Generating new puzzles, and minimising existing ones.
+//!
+//! A puzzle is made by filling in a random solution and then taking away
+//! clues, in a random order, for as long as the solution stays unique.
+//! Clues are taken away in groups which keep the puzzle symmetric.  All
+//! the randomness comes from a seeded generator, so the same seed always
+//! gives the same puzzles.  Minimising takes away the clues of an existing
+//! puzzle in the same way, one at a time and in order.
+
+use super::backtrack::backtrack_with;
+use super::dlx::is_unique;
+use super::grid::{SCell, SGrid};
+use super::rating::{rate, Grade, Rating};
+
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// A small seedable random number generator (SplitMix64)
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to, but not including, `n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for n in (1..items.len()).rev() {
+            items.swap(n, self.below(n + 1));
+        }
+    }
+}
+
+/// The symmetry kept between the clues of a generated puzzle
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    /// Unchanged by a half turn about the centre
+    Rotational,
+    /// Unchanged by reflection in the leading diagonal
+    Diagonal,
+    /// Unchanged by reflection left to right
+    Mirror,
+}
+
+impl Symmetry {
+    /// The cells which must be clues if the given cell is
+    fn orbit(self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let other = match self {
+            Symmetry::None => (row, col),
+            Symmetry::Rotational => (8 - row, 8 - col),
+            Symmetry::Diagonal => (col, row),
+            Symmetry::Mirror => (row, 8 - col),
+        };
+        if other == (row, col) {
+            vec![(row, col)]
+        } else {
+            vec![(row, col), other]
+        }
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Symmetry::None),
+            "rotational" => Ok(Symmetry::Rotational),
+            "diagonal" => Ok(Symmetry::Diagonal),
+            "mirror" => Ok(Symmetry::Mirror),
+            _ => Err(format!("Unknown symmetry {}", s)),
+        }
+    }
+}
+
+pub struct Generator {
+    rng: Rng,
+    symmetry: Symmetry,
+}
+
+impl Generator {
+    pub fn new(seed: u64, symmetry: Symmetry) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            symmetry,
+        }
+    }
+
+    /// Fill in a random solution to the given grid, which will normally be
+    /// empty so that only its ruleset matters.
+    pub fn solution(&mut self, template: &SGrid) -> Option<SGrid> {
+        let rng = &mut self.rng;
+        backtrack_with(template, |values| rng.shuffle(values))
+    }
+
+    /// Generate a puzzle with a unique solution, to which no clue can be
+    /// removed (keeping the symmetry) without losing that uniqueness.
+    pub fn puzzle(&mut self, template: &SGrid) -> Option<SGrid> {
+        let solution = self.solution(template)?;
+        let mut orbits = Vec::new();
+        for row in 0..9 {
+            for col in 0..9 {
+                let orbit = self.symmetry.orbit(row, col);
+                if orbit.iter().all(|&pos| pos >= (row, col)) {
+                    orbits.push(orbit);
+                }
+            }
+        }
+        self.rng.shuffle(&mut orbits);
+        let clues = (0..9)
+            .flat_map(|row| (0..9).map(move |col| (row, col)))
+            .collect::<Vec<_>>();
+        let clues = strip(template, &solution, clues, orbits);
+        Some(with_clues(template, &solution, &clues))
+    }
+
+    /// Generate puzzles until one is graded within the given band, giving
+    /// up after `attempts` tries.
+    pub fn puzzle_in(
+        &mut self,
+        template: &SGrid,
+        grades: RangeInclusive<Grade>,
+        attempts: usize,
+    ) -> Option<(SGrid, Rating)> {
+        for _ in 0..attempts {
+            let puzzle = self.puzzle(template)?;
+            if let Some(rating) = rate(&puzzle) {
+                if grades.contains(&rating.grade) {
+                    return Some((puzzle, rating));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Remove clues from a puzzle, one at a time and in order, for as long as
+/// its solution stays unique.  Returns the minimal puzzle and how many
+/// clues were removed, or None if the puzzle's solution is not unique.
+pub fn minimise(puzzle: &SGrid) -> Option<(SGrid, usize)> {
+    if !is_unique(puzzle) {
+        return None;
+    }
+    let template = puzzle.blank();
+    let clues = (0..9)
+        .flat_map(|row| (0..9).map(move |col| (row, col)))
+        .filter(|&(row, col)| matches!(puzzle.cell(row, col), SCell::Fixed(_)))
+        .collect::<Vec<_>>();
+    let orbits = clues.iter().map(|&pos| vec![pos]).collect();
+    let kept = strip(&template, puzzle, clues.clone(), orbits);
+    Some((
+        with_clues(&template, puzzle, &kept),
+        clues.len() - kept.len(),
+    ))
+}
+
+/// Take away each group of clues in turn, unless the solution would no
+/// longer be unique without them.  Returns the clues left.
+fn strip(
+    template: &SGrid,
+    solution: &SGrid,
+    mut clues: Vec<(usize, usize)>,
+    orbits: Vec<Vec<(usize, usize)>>,
+) -> Vec<(usize, usize)> {
+    for orbit in orbits {
+        let fewer = clues
+            .iter()
+            .copied()
+            .filter(|pos| !orbit.contains(pos))
+            .collect::<Vec<_>>();
+        if is_unique(&with_clues(template, solution, &fewer)) {
+            clues = fewer;
+        }
+    }
+    clues
+}
+
+/// The template grid with the given cells of the solution filled in
+fn with_clues(template: &SGrid, solution: &SGrid, clues: &[(usize, usize)]) -> SGrid {
+    let mut grid = template.clone();
+    for &(row, col) in clues {
+        if let SCell::Fixed(value) = solution.cell(row, col) {
+            grid.set_cell(row, col, value);
+        }
+    }
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Normal;
+    use crate::test_grid;
+
+    const SYMMETRIES: [Symmetry; 4] = [
+        Symmetry::None,
+        Symmetry::Rotational,
+        Symmetry::Diagonal,
+        Symmetry::Mirror,
+    ];
+
+    fn clues(grid: &SGrid) -> Vec<(usize, usize)> {
+        (0..9)
+            .flat_map(|row| (0..9).map(move |col| (row, col)))
+            .filter(|&(row, col)| matches!(grid.cell(row, col), SCell::Fixed(_)))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_puzzle() {
+        let template = SGrid::new(Normal::new());
+        let puzzle = |seed| {
+            let mut generator = Generator::new(seed, Symmetry::Rotational);
+            generator.puzzle(&template).unwrap().to_string()
+        };
+        assert_eq!(puzzle(42), puzzle(42));
+        assert_ne!(puzzle(42), puzzle(43));
+    }
+
+    #[test]
+    fn puzzles_are_unique_and_symmetric() {
+        let template = SGrid::new(Normal::new());
+        for (seed, &symmetry) in SYMMETRIES.iter().enumerate() {
+            let mut generator = Generator::new(seed as u64, symmetry);
+            let puzzle = generator.puzzle(&template).unwrap();
+            assert!(is_unique(&puzzle), "{:?}", symmetry);
+            let clues = clues(&puzzle);
+            for &(row, col) in &clues {
+                for pos in symmetry.orbit(row, col) {
+                    assert!(clues.contains(&pos), "{:?} at {:?}", symmetry, pos);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn puzzle_in_is_rated_within_band() {
+        let template = SGrid::new(Normal::new());
+        let band = Grade::Medium..=Grade::Hard;
+        let mut generator = Generator::new(7, Symmetry::Rotational);
+        let (puzzle, rating) = generator.puzzle_in(&template, band.clone(), 50).unwrap();
+        assert!(band.contains(&rating.grade));
+        assert!(is_unique(&puzzle));
+        let again = rate(&puzzle).unwrap();
+        assert_eq!(again.max, rating.max);
+        assert!(band.contains(&Grade::of(again.max)));
+    }
+
+    #[test]
+    fn minimised_puzzle_is_minimal() {
+        // The first grid of grids.txt, which has more clues than it needs
+        let puzzle = test_grid(
+            Normal::new(),
+            "...26.7.168..7..9.19...45..82.1...4...46.29...5...3.28..93...74.4..5..367.3.18...",
+        );
+        let (minimal, removed) = minimise(&puzzle).unwrap();
+        let (before, after) = (clues(&puzzle), clues(&minimal));
+        assert!(removed > 0);
+        assert_eq!(before.len() - after.len(), removed);
+        assert!(after.iter().all(|pos| before.contains(pos)));
+        assert!(is_unique(&minimal));
+        for &(row, col) in &after {
+            assert_eq!(minimal.cell(row, col), puzzle.cell(row, col));
+            let fewer = after
+                .iter()
+                .copied()
+                .filter(|&pos| pos != (row, col))
+                .collect::<Vec<_>>();
+            let grid = with_clues(&minimal.blank(), &minimal, &fewer);
+            assert!(!is_unique(&grid), "r{}c{} is not needed", row + 1, col + 1);
+        }
+
+        let blank = SGrid::new(Normal::new());
+        assert!(minimise(&blank).is_none());
+    }
+}
@@ -29,7 +29,7 @@ impl std::fmt::Debug for SCell {
 }
 
 impl Default for SCell {
-    #[allow(clippy::inconsistent_digit_grouping)]
+    #[allow(clippy::unusual_byte_groupings)]
     fn default() -> Self {
         SCell::Possible(0b111_111_111_0)
     }
@@ -139,9 +139,21 @@ impl Iterator for CellValues {
 }
 impl ExactSizeIterator for CellValues {}
 
+/// A point in a grid's history which it can be rolled back to
+#[derive(Copy, Clone, Debug)]
+pub struct Checkpoint(usize);
+
+/// Cloning a grid shares its ruleset, and copies its history
+#[derive(Clone)]
 pub struct SGrid {
     cells: [SCell; 81],
     rules: Rc<dyn Ruleset>,
+    /// The previous content of each cell altered since the outermost
+    /// open checkpoint was taken, oldest first
+    history: Vec<(usize, SCell)>,
+    /// How many checkpoints are open.  Changes are only recorded while
+    /// there are any.
+    checkpoints: usize,
 }
 
 impl std::fmt::Display for SGrid {
@@ -173,6 +185,18 @@ impl SGrid {
         Self {
             cells: [SCell::default(); 81],
             rules: Rc::new(rules),
+            history: Vec::new(),
+            checkpoints: 0,
+        }
+    }
+
+    /// An empty grid with the same ruleset
+    pub fn blank(&self) -> Self {
+        Self {
+            cells: [SCell::default(); 81],
+            rules: self.rules.clone(),
+            history: Vec::new(),
+            checkpoints: 0,
         }
     }
 
@@ -188,18 +212,67 @@ impl SGrid {
         self.cells[self._pos(row, col)]
     }
 
-    pub fn cell_mut(&mut self, row: usize, col: usize) -> &mut SCell {
-        &mut self.cells[self._pos(row, col)]
+    /// Every change to a cell is made here, so that while a checkpoint
+    /// is open it is recorded in the history and can be rolled back.
+    /// Returns true if the cell changed.
+    fn change(&mut self, pos: usize, cell: SCell) -> bool {
+        let old = self.cells[pos];
+        if old == cell {
+            return false;
+        }
+        if self.checkpoints > 0 {
+            self.history.push((pos, old));
+        }
+        self.cells[pos] = cell;
+        true
+    }
+
+    /// Remove a possibility from a cell, returning true if it was there
+    pub fn remove(&mut self, row: usize, col: usize, val: u8) -> bool {
+        let mut cell = self.cell(row, col);
+        cell.remove(val) && self.change(self._pos(row, col), cell)
     }
 
+    /// Start recording changes to the grid, so that they can be undone
+    /// by rolling back to the returned checkpoint.  A checkpoint stays
+    /// open, and may be rolled back to more than once, until it is
+    /// released.  Checkpoints must be released innermost first.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints += 1;
+        Checkpoint(self.history.len())
+    }
+
+    /// Undo every change made since the checkpoint was taken
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        while self.history.len() > checkpoint.0 {
+            let (pos, cell) = self.history.pop().unwrap();
+            self.cells[pos] = cell;
+        }
+    }
+
+    /// Close a checkpoint, keeping the changes made since it was taken.
+    /// Once the outermost checkpoint is released the history is dropped
+    /// and changes are no longer recorded.
+    pub fn release(&mut self, checkpoint: Checkpoint) {
+        debug_assert!(self.checkpoints > 0 && checkpoint.0 <= self.history.len());
+        self.checkpoints -= 1;
+        if self.checkpoints == 0 {
+            self.history.clear();
+        }
+    }
+
+    /// Whether the grid is finished, still being solved, or broken by a
+    /// cell with no possibilities left (`Insoluable`)
     pub fn done(&self) -> SResult {
-        for cell in &self.cells {
+        let mut ret = SResult::Finished;
+        for (pos, cell) in self.cells.iter().enumerate() {
             match cell {
                 SCell::Fixed(_) => continue,
-                SCell::Possible(_) => return SResult::Continue,
+                SCell::Possible(0) => return SResult::Insoluable(pos / 9, pos % 9),
+                SCell::Possible(_) => ret = SResult::Continue,
             }
         }
-        SResult::Finished
+        ret
     }
 
     pub fn set_cell(&mut self, row: usize, col: usize, val: u8) -> SResult {
@@ -228,15 +301,17 @@ impl SGrid {
                      * from anything we we can see.  If that results in a cell which
                      * cannot be anything, we're insoluable.
                      */
-                    *self.cell_mut(row, col) = SCell::Fixed(val);
+                    self.change(self._pos(row, col), SCell::Fixed(val));
                     for pos in self.rules.clone().sees(row, col) {
-                        match self.cell_mut(pos.0, pos.1) {
+                        match self.cell(pos.0, pos.1) {
                             SCell::Fixed(v) => {
                                 debug!("Cell at row {} col {} already fixed as {}", pos.0, pos.1, v)
                             }
-                            p => {
+                            mut p => {
                                 debug!("Removing from cell at row {} col {}", pos.0, pos.1);
-                                p.remove(val);
+                                if p.remove(val) {
+                                    self.change(self._pos(pos.0, pos.1), p);
+                                }
                                 if p.values().len() == 0 {
                                     return SResult::Insoluable(pos.0, pos.1);
                                 }
@@ -249,70 +324,101 @@ impl SGrid {
         }
     }
 
-    pub fn row_house(&self, row: usize) -> [SCell; 9] {
-        let mut ret = [SCell::default(); 9];
-        ret.iter_mut()
-            .enumerate()
-            .for_each(|(col, cell)| *cell = self.cell(row, col));
-        ret
-    }
-
-    pub fn col_house(&self, col: usize) -> [SCell; 9] {
+    /// The content of a house, in the order the ruleset lists its cells
+    pub fn house(&self, house: usize) -> [SCell; 9] {
         let mut ret = [SCell::default(); 9];
-        ret.iter_mut()
-            .enumerate()
-            .for_each(|(row, cell)| *cell = self.cell(row, col));
+        for (n, &(row, col)) in self.rules.houses()[house].iter().enumerate() {
+            ret[n] = self.cell(row, col);
+        }
         ret
     }
 
-    pub fn box_house(&self, _box: usize) -> [SCell; 9] {
-        let mut ret = [SCell::default(); 9];
-        for (n, (row, col)) in super::BOXES[_box].iter().enumerate() {
-            ret[n] = self.cell(*row, *col);
-        }
-        ret
+    pub fn house_cell_to_row_col(&self, house: usize, cell: usize) -> (usize, usize) {
+        self.rules.houses()[house][cell]
     }
 
-    pub fn house(&self, house: usize) -> [SCell; 9] {
-        match house {
-            0..=8 => self.row_house(house),
-            9..=17 => self.col_house(house - 9),
-            18..=26 => self.box_house(house - 18),
-            _ => unreachable!(),
-        }
+    /// Replace the content of a cell, returning true if it changed
+    pub fn alter_house(&mut self, house: usize, cell: usize, val: SCell) -> bool {
+        let (row, col) = self.house_cell_to_row_col(house, cell);
+        self.change(self._pos(row, col), val)
     }
 
-    pub fn house_cell_to_row_col(house: usize, cell: usize) -> (usize, usize) {
-        match house {
-            0..=8 => (house, cell),
-            9..=17 => (cell, house - 9),
-            18..=26 => super::BOXES[house - 18][cell],
-            _ => unreachable!(),
-        }
+    pub fn house_cell(&self, house: usize, cell: usize) -> SCell {
+        let (row, col) = self.house_cell_to_row_col(house, cell);
+        self.cell(row, col)
     }
+}
 
-    pub fn set_house(&mut self, house: usize, cell: usize, val: u8) -> SResult {
-        let (row, col) = Self::house_cell_to_row_col(house, cell);
-        self.set_cell(row, col, val)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtrack::backtrack;
+    use crate::rules::Normal;
+
+    #[test]
+    fn rollback_undoes_set_cell() {
+        let mut grid = SGrid::new(Normal::new());
+        let before = grid.cells;
+        let checkpoint = grid.checkpoint();
+        assert_eq!(grid.set_cell(4, 4, 5), SResult::Continue);
+        assert_eq!(grid.cell(4, 4), SCell::Fixed(5));
+        assert!(!grid.cell(4, 0).has(5));
+        // Only the cell and the twenty cells it sees changed
+        assert_eq!(grid.history.len(), 21);
+        grid.rollback(checkpoint);
+        assert!(grid.cells == before);
+        grid.release(checkpoint);
+        assert!(grid.history.is_empty());
+        assert_eq!(grid.checkpoints, 0);
     }
 
-    pub fn alter_house(&mut self, house: usize, cell: usize, val: SCell) -> bool {
-        let (row, col) = Self::house_cell_to_row_col(house, cell);
-        if self.cell(row, col) != val {
-            *self.cell_mut(row, col) = val;
-            true
-        } else {
-            false
-        }
+    #[test]
+    fn nested_rollback() {
+        let mut grid = SGrid::new(Normal::new());
+        let start = grid.cells;
+        let outer = grid.checkpoint();
+        grid.set_cell(0, 0, 1);
+        let middle = grid.cells;
+        let inner = grid.checkpoint();
+        grid.set_cell(8, 8, 2);
+        assert!(grid.remove(0, 8, 3));
+        grid.rollback(inner);
+        assert!(grid.cells == middle);
+        // An open checkpoint can be rolled back to again
+        grid.set_cell(1, 1, 4);
+        grid.rollback(inner);
+        assert!(grid.cells == middle);
+        grid.release(inner);
+        assert!(!grid.history.is_empty());
+        grid.rollback(outer);
+        assert!(grid.cells == start);
+        grid.release(outer);
+        assert!(grid.history.is_empty());
     }
 
-    pub fn house_cell(&self, house: usize, cell: usize) -> SCell {
-        let (row, col) = Self::house_cell_to_row_col(house, cell);
-        self.cell(row, col)
+    #[test]
+    fn records_only_changes() {
+        let mut grid = SGrid::new(Normal::new());
+        let checkpoint = grid.checkpoint();
+        assert!(grid.remove(0, 0, 1));
+        assert!(!grid.remove(0, 0, 1));
+        assert!(!grid.alter_house(0, 1, grid.house_cell(0, 1)));
+        assert_eq!(grid.history.len(), 1);
+        assert!(grid.alter_house(9, 2, SCell::Possible(0b110)));
+        assert_eq!(grid.cell(2, 0), SCell::Possible(0b110));
+        grid.rollback(checkpoint);
+        assert!(grid.cell(2, 0) == SCell::default() && grid.cell(0, 0) == SCell::default());
+        grid.release(checkpoint);
+        // Nothing is recorded with no checkpoint open
+        grid.remove(0, 0, 1);
+        assert!(grid.history.is_empty());
     }
 
-    pub fn house_cell_mut(&mut self, house: usize, cell: usize) -> &mut SCell {
-        let (row, col) = Self::house_cell_to_row_col(house, cell);
-        self.cell_mut(row, col)
+    #[test]
+    fn backtracking_leaves_no_history() {
+        let solution = backtrack(&SGrid::new(Normal::new())).unwrap();
+        assert_eq!(solution.done(), SResult::Finished);
+        assert!(solution.history.is_empty());
+        assert_eq!(solution.checkpoints, 0);
     }
 }
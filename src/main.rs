@@ -1,5 +1,11 @@
+mod backtrack;
+mod chain;
+mod dlx;
+mod generate;
 mod grid;
+mod rating;
 mod rules;
+mod step;
 mod technique;
 mod types;
 
@@ -30,14 +36,216 @@ fn apply(grid: &mut SGrid, input: &str) -> SResult {
     SResult::Continue
 }
 
-fn solve_grid(mut grid: SGrid) -> bool {
+/// Build a grid from 81 characters of input, for tests
+#[cfg(test)]
+fn test_grid<R: Ruleset + 'static>(rules: R, input: &str) -> SGrid {
+    let mut grid = SGrid::new(rules);
+    assert_eq!(apply(&mut grid, input), SResult::Continue);
+    grid
+}
+
+#[derive(Default)]
+struct Options {
+    assume_unique: bool,
+    complete: bool,
+    count: bool,
+    hint: bool,
+    rate: bool,
+    minimise: bool,
+    generate: Option<usize>,
+    seed: Option<u64>,
+    symmetry: Option<generate::Symmetry>,
+    grade: Option<rating::Grade>,
+    x_sudoku: bool,
+    anti_knight: bool,
+    anti_king: bool,
+    windoku: bool,
+}
+
+impl Options {
+    /// The extra constraints chosen, to be added to every ruleset
+    fn constraints(&self) -> Vec<&'static dyn Constraint> {
+        let mut ret: Vec<&'static dyn Constraint> = Vec::new();
+        if self.anti_knight {
+            ret.push(&AntiKnight);
+        }
+        if self.anti_king {
+            ret.push(&AntiKing);
+        }
+        if self.windoku {
+            ret.push(&Windoku);
+        }
+        ret
+    }
+}
+
+/// An empty grid with the given rules, and any constraints chosen
+fn constrained_grid(base: &dyn Ruleset, options: &Options) -> SGrid {
+    let rules = options
+        .constraints()
+        .into_iter()
+        .fold(CompositeRuleset::new(base), CompositeRuleset::with);
+    SGrid::new(rules)
+}
+
+/// An empty grid with the ruleset chosen by the options
+fn blank_grid(options: &Options) -> SGrid {
+    if options.x_sudoku {
+        constrained_grid(&XSudoku::new(), options)
+    } else {
+        constrained_grid(&Normal::new(), options)
+    }
+}
+
+/// An empty grid with the given jigsaw regions in place of the boxes, and
+/// the diagonals and any constraints chosen
+fn jigsaw_grid(regions: &Jigsaw, options: &Options) -> SGrid {
+    if options.x_sudoku {
+        constrained_grid(&regions.with(&Diagonal), options)
+    } else {
+        constrained_grid(regions, options)
+    }
+}
+
+fn minimise_grid(grid: &SGrid) -> bool {
+    println!("Grid:\n{}", grid);
+    match generate::minimise(grid) {
+        Some((puzzle, removed)) => {
+            println!(
+                "Minimal puzzle, with {} clues removed:\n{}",
+                removed, puzzle
+            );
+            println!("{}", grid_line(&puzzle));
+            true
+        }
+        None => {
+            println!("Grid does not have a unique solution");
+            false
+        }
+    }
+}
+
+/// The grid as a single line of 81 characters, with dots for blanks
+fn grid_line(grid: &SGrid) -> String {
+    (0..81)
+        .map(|n| match grid.cell(n / 9, n % 9) {
+            SCell::Fixed(v) => (b'0' + v) as char,
+            SCell::Possible(_) => '.',
+        })
+        .collect()
+}
+
+/// How many puzzles to try for each one asked for in a difficulty band
+const GENERATE_ATTEMPTS: usize = 100;
+
+fn generate_grids(count: usize, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let seed = match options.seed {
+        Some(seed) => seed,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_nanos() as u64,
+    };
+    let symmetry = options.symmetry.unwrap_or(generate::Symmetry::Rotational);
+    println!("# Generated with seed {} and {:?} symmetry", seed, symmetry);
+    let mut generator = generate::Generator::new(seed, symmetry);
+    let template = blank_grid(options);
+    for n in 0..count {
+        let generated = match options.grade {
+            Some(grade) => generator.puzzle_in(&template, grade..=grade, GENERATE_ATTEMPTS),
+            None => generator
+                .puzzle(&template)
+                .and_then(|puzzle| rating::rate(&puzzle).map(|rating| (puzzle, rating))),
+        };
+        match generated {
+            Some((puzzle, rating)) => {
+                println!("# Puzzle {}, rated {}", n + 1, rating);
+                println!("{}", grid_line(&puzzle));
+            }
+            None => println!("# Puzzle {}, none found", n + 1),
+        }
+    }
+    Ok(())
+}
+
+fn rate_grid(grid: &SGrid) -> bool {
+    println!("Grid:\n{}", grid);
+    match rating::rate(grid) {
+        Some(rating) => {
+            println!("Rating: {}", rating);
+            true
+        }
+        None => {
+            println!("Grid has no solution");
+            false
+        }
+    }
+}
+
+fn hint_grid(grid: &SGrid, options: &Options) -> bool {
     println!("Grid:\n{}", grid);
     let mut solver = SolverSet::full();
+    solver.assume_unique(options.assume_unique);
+    match grid.done() {
+        SResult::Finished => {
+            println!("Grid is already solved");
+            return true;
+        }
+        SResult::Continue => {}
+        e => {
+            println!("Grid has no solution: {:?}", e);
+            return false;
+        }
+    }
+    match solver.hint(grid) {
+        Some(step) => {
+            println!("Hint: {}", step);
+            true
+        }
+        None => {
+            println!("No hint available");
+            false
+        }
+    }
+}
+
+/// Stop counting solutions once this many have been found
+const COUNT_LIMIT: usize = 1000;
+
+fn count_grid(grid: &SGrid) -> bool {
+    println!("Grid:\n{}", grid);
+    match dlx::count_solutions(grid, COUNT_LIMIT) {
+        0 => println!("Grid has no solutions"),
+        1 => {
+            println!("Grid has a unique solution");
+            return true;
+        }
+        COUNT_LIMIT => println!("Grid has at least {} solutions", COUNT_LIMIT),
+        n => println!("Grid has {} solutions", n),
+    }
+    false
+}
+
+fn solve_grid(mut grid: SGrid, options: &Options) -> bool {
+    println!("Grid:\n{}", grid);
+    let mut solver = if options.complete {
+        SolverSet::complete()
+    } else {
+        SolverSet::full()
+    };
+    solver.assume_unique(options.assume_unique);
     match solver.solve_grid(&mut grid) {
-        SolveStepResult::Failed(e) => panic!("{:?}", e),
+        SolveStepResult::Failed(e) => {
+            println!("Grid has no solution: {:?}", e);
+            return false;
+        }
         SolveStepResult::Stuck => {
             println!("Failed");
-            solver.dump_actions();
+            match dlx::count_solutions(&grid, 2) {
+                0 => println!("Grid has no solutions"),
+                1 => println!("Grid has a unique solution"),
+                _ => println!("Grid has multiple solutions"),
+            }
+            solver.dump_log();
             eprintln!("Grid insoluable.  Final state:\n{}", grid);
             for row in 0..9 {
                 for col in 0..9 {
@@ -50,32 +258,92 @@ fn solve_grid(mut grid: SGrid) -> bool {
             return false;
         }
         SolveStepResult::Finished => {}
-        SolveStepResult::Acted => unreachable!(),
+        SolveStepResult::Acted(_) => unreachable!(),
     }
     println!("Finished grid:\n{}", grid);
-    solver.dump_actions();
+    println!("Solved in {} steps:", solver.log().len());
+    solver.dump_log();
     true
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     pretty_env_logger::init_custom_env("SUDOKU_LOG");
 
-    let fname = std::env::args_os()
-        .nth(1)
-        .unwrap_or_else(|| "grids.txt".into());
+    let mut options = Options::default();
+    let mut fname = None;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--generate" || arg == "--seed" || arg == "--symmetry" || arg == "--grade" {
+            let value = args
+                .next()
+                .and_then(|value| value.into_string().ok())
+                .ok_or_else(|| format!("{:?} needs a value", arg))?;
+            if arg == "--generate" {
+                options.generate = Some(value.parse()?);
+            } else if arg == "--seed" {
+                options.seed = Some(value.parse()?);
+            } else if arg == "--symmetry" {
+                options.symmetry = Some(value.parse()?);
+            } else {
+                options.grade = Some(value.parse()?);
+            }
+        } else if arg == "--assume-unique" {
+            options.assume_unique = true;
+        } else if arg == "--complete" {
+            options.complete = true;
+        } else if arg == "--count" {
+            options.count = true;
+        } else if arg == "--hint" {
+            options.hint = true;
+        } else if arg == "--rate" {
+            options.rate = true;
+        } else if arg == "--minimise" {
+            options.minimise = true;
+        } else if arg == "--x-sudoku" {
+            options.x_sudoku = true;
+        } else if arg == "--anti-knight" {
+            options.anti_knight = true;
+        } else if arg == "--anti-king" {
+            options.anti_king = true;
+        } else if arg == "--windoku" {
+            options.windoku = true;
+        } else {
+            fname = Some(arg);
+        }
+    }
+    if let Some(count) = options.generate {
+        return generate_grids(count, &options);
+    }
+    let fname = fname.unwrap_or_else(|| "grids.txt".into());
     let input = File::open(fname)?;
     let input = BufReader::new(input);
     let mut grids = Vec::new();
     let mut gridlines = String::new();
+    // A jigsaw region map (see `Jigsaw`) may come before a grid, in which
+    // case the grid uses those regions in place of the boxes.  Its lines
+    // hold letters, and no digits or dots.
+    let mut regionlines = String::new();
     for line in input.lines() {
         let line = line?;
         if line.starts_with('#') {
             continue;
         }
+        if line.chars().any(|c| c.is_ascii_alphabetic()) {
+            if line.chars().any(|c| c == '.' || c.is_ascii_digit()) {
+                return Err(format!("Line {:?} mixes a grid and a region map", line).into());
+            }
+            regionlines.push_str(&line);
+            continue;
+        }
         gridlines.extend(line.chars().filter(|&c| ". 123456789".contains(c)));
         match gridlines.len() {
-            n if n == 81 => {
-                let mut grid = SGrid::new(Normal::new());
+            81 => {
+                let mut grid = if regionlines.is_empty() {
+                    blank_grid(&options)
+                } else {
+                    jigsaw_grid(&regionlines.parse()?, &options)
+                };
+                regionlines = String::new();
                 if apply(&mut grid, &gridlines) != SResult::Continue {
                     panic!("Could not build grid from input");
                 }
@@ -93,7 +361,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let gridcount = grids.len();
     for (n, grid) in grids.into_iter().enumerate() {
         println!("Grid {}...", n + 1);
-        if !solve_grid(grid) {
+        let ok = if options.count {
+            count_grid(&grid)
+        } else if options.hint {
+            hint_grid(&grid, &options)
+        } else if options.rate {
+            rate_grid(&grid)
+        } else if options.minimise {
+            minimise_grid(&grid)
+        } else {
+            solve_grid(grid, &options)
+        };
+        if !ok {
             failcount += 1;
         }
     }
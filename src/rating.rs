@@ -0,0 +1,247 @@
+//! Rating how hard a grid is to solve.
+//!
+//! A grid is solved with the complete solver set, reordered so that the
+//! easiest technique available is always used, and rated by the hardest
+//! step it needed.  The difficulties are those of Sudoku Explainer (see
+//! [`Technique::difficulty`]), so ratings should be comparable with its
+//! SE numbers.
+//!
+//! [`Technique::difficulty`]: super::technique::Technique::difficulty
+
+use super::dlx::is_unique;
+use super::grid::SGrid;
+use super::technique::{SolveStepResult, SolverSet};
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Grade {
+    /// Singles only
+    Easy,
+    /// Locked candidates, pairs and X-Wings
+    Medium,
+    /// Triples, Swordfish, wings and unique rectangles
+    Hard,
+    /// Quads, Jellyfish, BUG+1 and short chains
+    Fiendish,
+    /// Longer chains, almost locked sets and forcing chains
+    Diabolical,
+}
+
+impl Grade {
+    /// The grade of a grid whose hardest step has the given difficulty.
+    ///
+    /// Each band ends at the Sudoku Explainer difficulty of the hardest
+    /// technique listed for it: naked singles (2.3), hidden pairs (3.4),
+    /// unique rectangle type 3 and hidden unique rectangles (4.7), and the
+    /// single-digit patterns such as skyscrapers and X-chains (6.6).
+    pub fn of(difficulty: f32) -> Self {
+        if difficulty <= 2.3 {
+            Grade::Easy
+        } else if difficulty <= 3.4 {
+            Grade::Medium
+        } else if difficulty <= 4.7 {
+            Grade::Hard
+        } else if difficulty <= 6.6 {
+            Grade::Fiendish
+        } else {
+            Grade::Diabolical
+        }
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Grade::Easy => "easy",
+            Grade::Medium => "medium",
+            Grade::Hard => "hard",
+            Grade::Fiendish => "fiendish",
+            Grade::Diabolical => "diabolical",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Grade {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Grade::Easy),
+            "medium" => Ok(Grade::Medium),
+            "hard" => Ok(Grade::Hard),
+            "fiendish" => Ok(Grade::Fiendish),
+            "diabolical" => Ok(Grade::Diabolical),
+            _ => Err(format!("Unknown grade {}", s)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Rating {
+    /// The difficulty of the hardest step
+    pub max: f32,
+    /// The sum of the difficulties of every step
+    pub total: f32,
+    /// The number of steps taken
+    pub steps: usize,
+    pub grade: Grade,
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} ({}), scoring {:.1} over {} steps",
+            self.max, self.grade, self.total, self.steps
+        )
+    }
+}
+
+/// Rate a grid.  Uniqueness techniques are used if the grid has a single
+/// solution.  Returns None if the grid has no solution.
+pub fn rate(grid: &SGrid) -> Option<Rating> {
+    let mut grid = grid.clone();
+    let mut solver = SolverSet::complete();
+    solver.by_difficulty();
+    solver.assume_unique(is_unique(&grid));
+    match solver.solve_grid(&mut grid) {
+        SolveStepResult::Finished => {}
+        _ => return None,
+    }
+    let max = solver
+        .log()
+        .iter()
+        .map(|step| step.difficulty)
+        .fold(0.0, f32::max);
+    Some(Rating {
+        max,
+        total: solver.log().iter().map(|step| step.difficulty).sum(),
+        steps: solver.log().len(),
+        grade: Grade::of(max),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Normal;
+    use crate::technique::*;
+    use crate::test_grid;
+
+    // Puzzles from the bundled corpora whose hardest step is a technique
+    // Sudoku Explainer rates at a fixed difficulty.  Explainer always
+    // takes the easiest step available, so its rating of each is that of
+    // the technique named, and these cover each side of the first three
+    // grade boundaries.
+    const EXPLAINER: &[(&str, f32, Grade)] = &[
+        // Hidden singles
+        (
+            "...........98.51...519.742.29.4.1.65.........14.5.8.93.267.958...51.36...........",
+            1.2,
+            Grade::Easy,
+        ),
+        (
+            "....8....27.....54.95...81...98.64...2.4.3.6...69.51...17...62.46.....38....9....",
+            1.2,
+            Grade::Easy,
+        ),
+        (
+            "..........79.5.18.8.......7..73.68..45.7.8.96..35.27..7.......5.16.3.42..........",
+            1.5,
+            Grade::Easy,
+        ),
+        (
+            ".......8.8..7.1.4..4..2..3.374...9......3......5...321.1..6..5..5.8.2..6.8.......",
+            1.5,
+            Grade::Easy,
+        ),
+        // Naked single
+        (
+            "......9.7...42.18....7.5.261..9.4....5.....4....5.7..992.1.8....34.59...5.7......",
+            2.3,
+            Grade::Easy,
+        ),
+        // Hidden pair
+        (
+            "45.....3....8.1....9...........5..9.2..7.....8.........1..4..........7.2...6..8..",
+            3.4,
+            Grade::Medium,
+        ),
+        // Naked triple
+        (
+            "1.....3.8.7.4..............2.3.1...........958.........5.6...7.....8.2...4.......",
+            3.6,
+            Grade::Hard,
+        ),
+        // XY-Wing
+        (
+            "..9.....3.....9...7.....5.6..65..4.....3......28......3..75.6..6...........12.3.8",
+            4.2,
+            Grade::Hard,
+        ),
+        // Unique rectangle type 4
+        (
+            "249.6...3.3....2..8.......5.....6......2......1..4.82..9.5..7....4.....1.7...3...",
+            4.5,
+            Grade::Hard,
+        ),
+        // BUG+1
+        (
+            ".8...4.5....7..3............1..85...6.....2......4....3.26............417........",
+            5.6,
+            Grade::Fiendish,
+        ),
+    ];
+
+    // Puzzles from newgrids.txt needing techniques which Explainer rates
+    // by the length of the chain it finds rather than at a fixed value,
+    // so only the grade can be checked: a skyscraper, a finned swordfish
+    // and a cell forcing chain either side of the last boundary.
+    const BEYOND_EXPLAINER: &[(&str, Grade)] = &[
+        (
+            "..5...987.4..5...1..7......2...48....9.1.....6..2.....3..6..2.......9.7.......5..",
+            Grade::Fiendish,
+        ),
+        (
+            "..........72.6.1....51...82.8...13..4.........37.9..1.....238..5.4..9.........79.",
+            Grade::Diabolical,
+        ),
+        (
+            "6.2.5.........3.4..........43...8....1....2........7..5..27...........81...6.....",
+            Grade::Diabolical,
+        ),
+    ];
+
+    #[test]
+    fn matches_explainer() {
+        for &(puzzle, se, grade) in EXPLAINER {
+            let rating = rate(&test_grid(Normal::new(), puzzle)).unwrap();
+            assert_eq!(rating.max, se, "{}", puzzle);
+            assert_eq!(rating.grade, grade, "{}", puzzle);
+        }
+    }
+
+    #[test]
+    fn grades_chain_rated_grids() {
+        for &(puzzle, grade) in BEYOND_EXPLAINER {
+            let rating = rate(&test_grid(Normal::new(), puzzle)).unwrap();
+            assert_eq!(rating.grade, grade, "{}", puzzle);
+        }
+    }
+
+    #[test]
+    fn grades_split_at_explainer_techniques() {
+        let grade = |technique: &dyn Technique| Grade::of(technique.difficulty());
+        assert_eq!(grade(&NakedSingle), Grade::Easy);
+        assert_eq!(grade(&Pointing), Grade::Medium);
+        assert_eq!(grade(&HiddenSubset::new(2)), Grade::Medium);
+        assert_eq!(grade(&NakedSubset::new(3)), Grade::Hard);
+        assert_eq!(grade(&UniqueRectangle::new(3)), Grade::Hard);
+        assert_eq!(grade(&NakedSubset::new(4)), Grade::Fiendish);
+        assert_eq!(grade(&XChain), Grade::Fiendish);
+        assert_eq!(grade(&XYChain), Grade::Diabolical);
+    }
+}
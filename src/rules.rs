@@ -1,32 +1,246 @@
+use std::ops::Range;
+use std::str::FromStr;
+
 pub trait Ruleset {
     fn sees(&self, row: usize, col: usize) -> &[(usize, usize)];
 
-    // Houses 0..9 are the rows
-    // Houses 9..18 are the columns
-    // Houses 18..27 are the boxes
-    fn overlapping_houses(&self, house: usize) -> &[usize] {
-        match house {
-            0 | 1 | 2 => &[18, 19, 20],    // Top three rows
-            3 | 4 | 5 => &[21, 22, 23],    // Next three
-            6 | 7 | 8 => &[24, 25, 26],    // Bottom three
-            9 | 10 | 11 => &[18, 21, 24],  // Left three boxes
-            12 | 13 | 14 => &[19, 22, 25], // Next three
-            15 | 16 | 17 => &[20, 23, 26], // Right three
-            18 => &[0, 1, 2, 9, 10, 11],   // Top left box
-            19 => &[0, 1, 2, 12, 13, 14],
-            20 => &[0, 1, 2, 15, 16, 17], // Top right box
-            21 => &[3, 4, 5, 9, 10, 11],
-            22 => &[3, 4, 5, 12, 13, 14],
-            23 => &[3, 4, 5, 15, 16, 17], // Middle right box
-            24 => &[6, 7, 8, 9, 10, 11],
-            25 => &[6, 7, 8, 12, 13, 14],
-            26 => &[6, 7, 8, 15, 16, 17], // Bottom right box
-            _ => unreachable!(),
-        }
-    }
-}
-
-pub static BOXES: &[[(usize, usize); 9]] = &[
+    // Every house, including the rows, columns and boxes.  Any houses
+    // outside those three ranges are particular to the ruleset.
+    fn houses(&self) -> &[[(usize, usize); 9]];
+
+    // The rows, in order, each listing its cells from left to right
+    fn rows(&self) -> Range<usize>;
+
+    // The columns, in order, each listing its cells from top to bottom
+    fn cols(&self) -> Range<usize>;
+
+    // The boxes (or regions)
+    fn boxes(&self) -> Range<usize>;
+
+    // The houses which share at least two cells with the given house
+    fn overlapping_houses(&self, house: usize) -> &[usize];
+
+    // These rules with a constraint added, such as
+    // `Normal::new().with(&AntiKnight)`
+    fn with(&self, constraint: &dyn Constraint) -> CompositeRuleset
+    where
+        Self: Sized,
+    {
+        CompositeRuleset::new(self).with(constraint)
+    }
+}
+
+/// The houses of a ruleset, along with which houses overlap, and which
+/// cells each cell sees (by sharing a house, or through a constraint).
+struct Houses {
+    cells: Vec<[(usize, usize); 9]>,
+    rows: Range<usize>,
+    cols: Range<usize>,
+    boxes: Range<usize>,
+    overlaps: Vec<Vec<usize>>,
+    sees: Vec<Vec<(usize, usize)>>,
+}
+
+impl Houses {
+    /// The rows and columns, followed by the given boxes
+    fn new(boxes: &[[(usize, usize); 9]]) -> Self {
+        let mut ret = Houses {
+            cells: Vec::new(),
+            rows: 0..9,
+            cols: 9..18,
+            boxes: 18..18 + boxes.len(),
+            overlaps: Vec::new(),
+            sees: vec![Vec::new(); 81],
+        };
+        for row in 0..9 {
+            let mut house = [(0, 0); 9];
+            for (col, pos) in house.iter_mut().enumerate() {
+                *pos = (row, col);
+            }
+            ret.add_house(house);
+        }
+        for col in 0..9 {
+            let mut house = [(0, 0); 9];
+            for (row, pos) in house.iter_mut().enumerate() {
+                *pos = (row, col);
+            }
+            ret.add_house(house);
+        }
+        for &house in boxes {
+            ret.add_house(house);
+        }
+        ret
+    }
+
+    /// Copy the houses and sees lists of an existing ruleset
+    fn of(rules: &dyn Ruleset) -> Self {
+        Houses {
+            cells: rules.houses().to_vec(),
+            rows: rules.rows(),
+            cols: rules.cols(),
+            boxes: rules.boxes(),
+            overlaps: (0..rules.houses().len())
+                .map(|house| rules.overlapping_houses(house).to_vec())
+                .collect(),
+            sees: (0..81)
+                .map(|pos| rules.sees(pos / 9, pos % 9).to_vec())
+                .collect(),
+        }
+    }
+
+    /// Add a house after the others, making its cells see each other
+    fn add_house(&mut self, house: [(usize, usize); 9]) {
+        let new = self.cells.len();
+        let overlaps = (0..new)
+            .filter(|&other| {
+                house
+                    .iter()
+                    .filter(|pos| self.cells[other].contains(pos))
+                    .count()
+                    >= 2
+            })
+            .collect::<Vec<_>>();
+        for &other in &overlaps {
+            self.overlaps[other].push(new);
+        }
+        self.overlaps.push(overlaps);
+        self.cells.push(house);
+        for &(row, col) in &house {
+            self.add_sees(row, col, &house);
+        }
+    }
+
+    fn add_sees(&mut self, row: usize, col: usize, cells: &[(usize, usize)]) {
+        let seen = &mut self.sees[(row * 9) + col];
+        for &pos in cells {
+            if pos != (row, col) && !seen.contains(&pos) {
+                seen.push(pos);
+            }
+        }
+    }
+}
+
+/// Implement `Ruleset` for rules which keep everything in a `houses` field
+macro_rules! impl_ruleset {
+    ($($rules:ty),*) => {
+        $(
+            impl Ruleset for $rules {
+                fn sees(&self, row: usize, col: usize) -> &[(usize, usize)] {
+                    &self.houses.sees[(row * 9) + col]
+                }
+
+                fn houses(&self) -> &[[(usize, usize); 9]] {
+                    &self.houses.cells
+                }
+
+                fn rows(&self) -> Range<usize> {
+                    self.houses.rows.clone()
+                }
+
+                fn cols(&self) -> Range<usize> {
+                    self.houses.cols.clone()
+                }
+
+                fn boxes(&self) -> Range<usize> {
+                    self.houses.boxes.clone()
+                }
+
+                fn overlapping_houses(&self, house: usize) -> &[usize] {
+                    &self.houses.overlaps[house]
+                }
+            }
+        )*
+    };
+}
+
+impl_ruleset!(Normal, XSudoku, Jigsaw, CompositeRuleset);
+
+/// A constraint which can be added to a ruleset (see [`CompositeRuleset`])
+pub trait Constraint {
+    /// Any houses the constraint adds
+    fn houses(&self) -> Vec<[(usize, usize); 9]> {
+        Vec::new()
+    }
+
+    /// The cells which a cell sees because of the constraint, other than
+    /// through its houses
+    fn peers(&self, _row: usize, _col: usize) -> Vec<(usize, usize)> {
+        Vec::new()
+    }
+}
+
+/// Diagonal: both main diagonals are houses, the leading diagonal first
+pub struct Diagonal;
+
+impl Constraint for Diagonal {
+    fn houses(&self) -> Vec<[(usize, usize); 9]> {
+        let mut leading = [(0, 0); 9];
+        let mut other = [(0, 0); 9];
+        for (n, (lpos, opos)) in leading.iter_mut().zip(other.iter_mut()).enumerate() {
+            *lpos = (n, n);
+            *opos = (n, 8 - n);
+        }
+        vec![leading, other]
+    }
+}
+
+/// Windoku: four extra 3x3 houses, each one cell in from a corner of the
+/// grid, in the order top left, top right, bottom left, bottom right
+pub struct Windoku;
+
+impl Constraint for Windoku {
+    fn houses(&self) -> Vec<[(usize, usize); 9]> {
+        let mut ret = Vec::new();
+        for &(top, left) in &[(1, 1), (1, 5), (5, 1), (5, 5)] {
+            let mut house = [(0, 0); 9];
+            for (n, pos) in house.iter_mut().enumerate() {
+                *pos = (top + n / 3, left + n % 3);
+            }
+            ret.push(house);
+        }
+        ret
+    }
+}
+
+/// The cells a given number of rows and columns away from a cell, in
+/// each direction, which are still on the grid
+fn offsets(row: usize, col: usize, steps: &[(isize, isize)]) -> Vec<(usize, usize)> {
+    let mut ret = Vec::new();
+    for &(drow, dcol) in steps {
+        for &(drow, dcol) in &[(drow, dcol), (-drow, dcol), (drow, -dcol), (-drow, -dcol)] {
+            let (orow, ocol) = (row as isize + drow, col as isize + dcol);
+            if (0..9).contains(&orow) && (0..9).contains(&ocol) {
+                let pos = (orow as usize, ocol as usize);
+                if !ret.contains(&pos) {
+                    ret.push(pos);
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Anti-knight: cells a chess knight's move apart cannot hold the same value
+pub struct AntiKnight;
+
+impl Constraint for AntiKnight {
+    fn peers(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        offsets(row, col, &[(1, 2), (2, 1)])
+    }
+}
+
+/// Anti-king: cells a chess king's move apart cannot hold the same value.
+/// Only the diagonal moves add anything, since the rest are in the same
+/// row or column.
+pub struct AntiKing;
+
+impl Constraint for AntiKing {
+    fn peers(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        offsets(row, col, &[(1, 1)])
+    }
+}
+
+static BOXES: &[[(usize, usize); 9]] = &[
     [
         (0, 0),
         (0, 1),
@@ -134,62 +348,308 @@ pub static BOXES: &[[(usize, usize); 9]] = &[
 /// need any data in the Normal struct, but we're lazy so we compute it on
 /// startup.
 pub struct Normal {
-    sees: Vec<Vec<(usize, usize)>>,
+    houses: Houses,
 }
 
 impl Normal {
-    fn boxcells(row: usize, col: usize) -> &'static [(usize, usize); 9] {
-        match row {
-            0 | 1 | 2 => match col {
-                0 | 1 | 2 => &BOXES[0],
-                3 | 4 | 5 => &BOXES[1],
-                6 | 7 | 8 => &BOXES[2],
-                _ => unimplemented!(),
-            },
-            3 | 4 | 5 => match col {
-                0 | 1 | 2 => &BOXES[3],
-                3 | 4 | 5 => &BOXES[4],
-                6 | 7 | 8 => &BOXES[5],
-                _ => unimplemented!(),
-            },
-            6 | 7 | 8 => match col {
-                0 | 1 | 2 => &BOXES[6],
-                3 | 4 | 5 => &BOXES[7],
-                6 | 7 | 8 => &BOXES[8],
-                _ => unimplemented!(),
-            },
-            _ => unimplemented!(),
+    pub fn new() -> Self {
+        Normal {
+            houses: Houses::new(BOXES),
         }
     }
+}
+
+/// Diagonal (X-Sudoku) rules
+///
+/// As normal, but the two main diagonals are also houses, so cells on a
+/// diagonal also see the rest of that diagonal.  The diagonals follow the
+/// boxes, the leading diagonal first.
+pub struct XSudoku {
+    houses: Houses,
+}
+
+impl XSudoku {
     pub fn new() -> Self {
-        let mut ret = Normal { sees: Vec::new() };
-        for row in 0..9 {
-            for col in 0..9 {
-                let mut seen = Vec::new();
-                for col2 in 0..9 {
-                    if col != col2 {
-                        seen.push((row, col2));
-                    }
-                }
-                for row2 in 0..9 {
-                    if row != row2 {
-                        seen.push((row2, col));
-                    }
+        XSudoku {
+            houses: Normal::new().with(&Diagonal).houses,
+        }
+    }
+}
+
+/// Jigsaw rules
+///
+/// As normal, but the boxes are replaced by irregular regions of nine
+/// cells, which take the place of the boxes.  A region map is written as one
+/// letter (A to I) per cell, row by row, where cells with the same letter
+/// are in the same region.  Anything else, such as whitespace, is ignored.
+pub struct Jigsaw {
+    houses: Houses,
+}
+
+impl Jigsaw {
+    /// Build the rules from the region (0..9) each cell is in, which must
+    /// put nine connected cells in each region.
+    pub fn new(regions: &[usize; 81]) -> Result<Self, String> {
+        let mut boxes = vec![Vec::new(); 9];
+        for (pos, &region) in regions.iter().enumerate() {
+            match boxes.get_mut(region) {
+                Some(cells) => cells.push((pos / 9, pos % 9)),
+                None => return Err(format!("Unknown region {}", region)),
+            }
+        }
+        let boxes = boxes
+            .into_iter()
+            .enumerate()
+            .map(|(region, cells)| {
+                let mut house = [(0, 0); 9];
+                if cells.len() != 9 {
+                    return Err(format!(
+                        "Region {} has {} cells",
+                        (b'A' + region as u8) as char,
+                        cells.len()
+                    ));
                 }
-                for &(brow, bcol) in Normal::boxcells(row, col) {
-                    if brow != row && bcol != col {
-                        seen.push((brow, bcol));
-                    }
+                if !connected(&cells) {
+                    return Err(format!(
+                        "Region {} is not connected",
+                        (b'A' + region as u8) as char
+                    ));
                 }
-                ret.sees.push(seen);
+                house.copy_from_slice(&cells);
+                Ok(house)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Jigsaw {
+            houses: Houses::new(&boxes),
+        })
+    }
+}
+
+/// Whether the cells can all be reached from the first by moving up, down,
+/// left or right without leaving them
+fn connected(cells: &[(usize, usize)]) -> bool {
+    let mut reached = cells.iter().take(1).copied().collect::<Vec<_>>();
+    let mut n = 0;
+    while n < reached.len() {
+        let (row, col) = reached[n];
+        for &pos in cells {
+            if row.max(pos.0) - row.min(pos.0) + col.max(pos.1) - col.min(pos.1) == 1
+                && !reached.contains(&pos)
+            {
+                reached.push(pos);
             }
         }
-        ret
+        n += 1;
+    }
+    reached.len() == cells.len()
+}
+
+impl FromStr for Jigsaw {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let letters = s
+            .chars()
+            .filter(|c| c.is_ascii_alphabetic())
+            .map(|c| c.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+        if letters.len() != 81 {
+            return Err(format!("Region map has {} cells", letters.len()));
+        }
+        let mut regions = [0; 81];
+        for (region, letter) in regions.iter_mut().zip(letters) {
+            if !('A'..='I').contains(&letter) {
+                return Err(format!("Unknown region {}", letter));
+            }
+            *region = (letter as u8 - b'A') as usize;
+        }
+        Jigsaw::new(&regions)
     }
 }
 
-impl Ruleset for Normal {
-    fn sees(&self, row: usize, col: usize) -> &[(usize, usize)] {
-        &self.sees[(row * 9) + col]
+/// Rules built up from another ruleset by adding constraints, such as
+/// `Normal::new().with(&Diagonal).with(&AntiKnight)`.
+/// Each constraint's houses follow those already there, other than any
+/// with the same cells as one already there, and the cells each cell sees
+/// are merged.
+pub struct CompositeRuleset {
+    houses: Houses,
+}
+
+impl CompositeRuleset {
+    pub fn new(base: &dyn Ruleset) -> Self {
+        CompositeRuleset {
+            houses: Houses::of(base),
+        }
+    }
+
+    pub fn with(mut self, constraint: &dyn Constraint) -> Self {
+        for house in constraint.houses() {
+            if !self
+                .houses
+                .cells
+                .iter()
+                .any(|other| house.iter().all(|pos| other.contains(pos)))
+            {
+                self.houses.add_house(house);
+            }
+        }
+        for row in 0..9 {
+            for col in 0..9 {
+                self.houses.add_sees(row, col, &constraint.peers(row, col));
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dlx;
+    use crate::grid::{SCell, SGrid};
+    use crate::technique::{SolveStepResult, SolverSet};
+    use std::collections::HashSet;
+
+    /// Check the rows, columns and boxes a ruleset reports are the houses
+    /// it says they are
+    fn check_roles(rules: &dyn Ruleset, boxes: &[[(usize, usize); 9]]) {
+        let houses = rules.houses();
+        for (row, house) in rules.rows().enumerate() {
+            assert!((0..9).all(|n| houses[house][n] == (row, n)));
+        }
+        for (col, house) in rules.cols().enumerate() {
+            assert!((0..9).all(|n| houses[house][n] == (n, col)));
+        }
+        assert_eq!(&houses[rules.boxes()], boxes);
+    }
+
+    #[test]
+    fn house_roles() {
+        check_roles(&Normal::new(), BOXES);
+        let x = XSudoku::new();
+        check_roles(&x, BOXES);
+        assert_eq!(x.houses().len(), 29);
+        check_roles(&Normal::new().with(&Windoku), BOXES);
+    }
+
+    const REGIONS: &str = "AAABBBCCC
+                           AAABBBCCC
+                           AAABBBCCC
+                           DDDEEEFFF
+                           DDDEEEFIF
+                           DDDEEHFIF
+                           GGGEHHIIF
+                           GGGHHHIIF
+                           GGGHHHIII";
+
+    #[test]
+    fn jigsaw_from_str() {
+        let rules = REGIONS.parse::<Jigsaw>().unwrap();
+        let boxes = &rules.houses()[rules.boxes()];
+        assert_eq!(boxes.len(), 9);
+        assert!(boxes[5].contains(&(6, 8)));
+        assert!(boxes[8].contains(&(4, 7)));
+        assert!(rules.sees(4, 7).contains(&(8, 8)));
+        assert!(!rules.sees(4, 7).contains(&(3, 6)));
+        assert_eq!(
+            REGIONS.to_lowercase().parse::<Jigsaw>().unwrap().houses(),
+            rules.houses()
+        );
+    }
+
+    #[test]
+    fn bad_jigsaws() {
+        let err = |map: &str| map.parse::<Jigsaw>().err().unwrap();
+        assert_eq!(err(&REGIONS[1..]), "Region map has 80 cells");
+        assert_eq!(err(&REGIONS.replacen('I', "J", 1)), "Unknown region J");
+        assert_eq!(err(&REGIONS.replacen('I', "H", 1)), "Region H has 10 cells");
+        // Swap a cell of D with one of E, cutting both off
+        let moved = REGIONS.replacen("DDDEEEFFF", "DDEDEEFFF", 1);
+        assert_eq!(err(&moved), "Region D is not connected");
+    }
+
+    /// The cells a cell sees under some rules but not under normal rules
+    fn gained(rules: &dyn Ruleset, row: usize, col: usize) -> HashSet<(usize, usize)> {
+        let normal = Normal::new();
+        let seen = rules.sees(row, col).iter().copied().collect::<HashSet<_>>();
+        assert!(normal.sees(row, col).iter().all(|pos| seen.contains(pos)));
+        &seen - &normal.sees(row, col).iter().copied().collect()
+    }
+
+    #[test]
+    fn chess_peers() {
+        let knight = Normal::new().with(&AntiKnight);
+        let king = Normal::new().with(&AntiKing);
+        // From a corner every knight and king move stays in the box
+        assert_eq!(knight.sees(0, 0).len(), 20);
+        assert!(knight.sees(0, 0).contains(&(1, 2)) && knight.sees(0, 0).contains(&(2, 1)));
+        assert!(gained(&knight, 0, 0).is_empty());
+        assert!(gained(&king, 0, 0).is_empty());
+        // From the centre every knight move leaves the box
+        let moves = [
+            (2, 3),
+            (2, 5),
+            (3, 2),
+            (3, 6),
+            (5, 2),
+            (5, 6),
+            (6, 3),
+            (6, 5),
+        ];
+        assert_eq!(gained(&knight, 4, 4), moves.iter().copied().collect());
+        assert_eq!(knight.sees(4, 4).len(), 28);
+        assert!(gained(&king, 4, 4).is_empty());
+        // From a box's corner, three of the king's diagonal moves leave it
+        let moves = [(1, 3), (3, 1), (3, 3)];
+        assert_eq!(gained(&king, 2, 2), moves.iter().copied().collect());
+        assert_eq!(gained(&king.with(&AntiKnight), 4, 4), gained(&knight, 4, 4));
+    }
+
+    #[test]
+    fn composite() {
+        let rules = Normal::new()
+            .with(&Diagonal)
+            .with(&AntiKnight)
+            .with(&Windoku);
+        assert_eq!(rules.houses().len(), 27 + 2 + 4);
+        check_roles(&rules, BOXES);
+        assert!(rules.sees(4, 4).contains(&(2, 5)));
+        assert!(rules.sees(2, 5).contains(&(4, 4)));
+        assert!(!rules.sees(4, 4).contains(&(1, 6)));
+        // No grid keeps all four sets of rules at once
+        assert_eq!(dlx::count_solutions(&SGrid::new(rules), 1), 0);
+        // The diagonals are already there, so nothing is added twice
+        let x = XSudoku::new().with(&Diagonal);
+        assert_eq!(x.houses(), XSudoku::new().houses());
+        assert_eq!(
+            x.overlapping_houses(27),
+            XSudoku::new().overlapping_houses(27)
+        );
+        assert_eq!(x.sees(4, 4), XSudoku::new().sees(4, 4));
+    }
+
+    #[test]
+    fn solves_composite() {
+        let rules = Normal::new().with(&AntiKnight).with(&Windoku);
+        let mut grid = crate::test_grid(
+            rules,
+            "..4.57.6.563....9.87.963.1...15....22.574.9.66.7..9.5...9.28.7.....74129....95438",
+        );
+        assert!(matches!(
+            SolverSet::full().solve_grid(&mut grid),
+            SolveStepResult::Finished
+        ));
+        let rules = grid.rules();
+        for row in 0..9 {
+            for col in 0..9 {
+                let value = grid.cell(row, col);
+                assert!(matches!(value, SCell::Fixed(_)));
+                assert!(rules
+                    .sees(row, col)
+                    .iter()
+                    .all(|&(r, c)| grid.cell(r, c) != value));
+            }
+        }
     }
 }
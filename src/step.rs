@@ -0,0 +1,164 @@
+//! Explanations of solving steps.
+//!
+//! When a technique acts it returns a [`Step`] which says what it found
+//! and what it did about it.  Techniques make their changes through the
+//! step, so that every placement and elimination is recorded as it is
+//! made.  Cells are shown in r1c1 notation, counting from one.
+
+use super::grid::{SCell, SGrid};
+use super::types::SResult;
+
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub struct Step {
+    /// The name of the technique which found the step
+    pub technique: &'static str,
+    /// The difficulty of the technique, filled in by the solver
+    pub difficulty: f32,
+    /// The cells which make up the pattern the technique found
+    pub cells: Vec<(usize, usize)>,
+    /// The digits the pattern is about
+    pub digits: Vec<u8>,
+    /// Cells which were fixed as a result of the step
+    pub placements: Vec<(usize, usize, u8)>,
+    /// Possibilities which were removed as a result of the step
+    pub eliminations: Vec<(usize, usize, u8)>,
+}
+
+impl Step {
+    pub fn new(technique: &'static str) -> Self {
+        Self {
+            technique,
+            difficulty: 0.0,
+            cells: Vec::new(),
+            digits: Vec::new(),
+            placements: Vec::new(),
+            eliminations: Vec::new(),
+        }
+    }
+
+    /// Add cells to the pattern, ignoring any already in it
+    pub fn with_cells<I>(mut self, cells: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        for cell in cells {
+            if !self.cells.contains(&cell) {
+                self.cells.push(cell);
+            }
+        }
+        self
+    }
+
+    /// Add digits to the pattern, ignoring any already in it
+    pub fn with_digits<I>(mut self, digits: I) -> Self
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        for digit in digits {
+            if !self.digits.contains(&digit) {
+                self.digits.push(digit);
+            }
+        }
+        self.digits.sort_unstable();
+        self
+    }
+
+    /// Whether the step has changed the grid yet
+    pub fn acted(&self) -> bool {
+        !(self.placements.is_empty() && self.eliminations.is_empty())
+    }
+
+    /// Fix a cell, as `SGrid::set_cell` does.  Only the placement is
+    /// recorded, not the possibilities it removes from the cells it sees.
+    pub fn place(&mut self, grid: &mut SGrid, row: usize, col: usize, value: u8) -> SResult {
+        if !matches!(grid.cell(row, col), SCell::Fixed(_)) {
+            self.placements.push((row, col, value));
+        }
+        grid.set_cell(row, col, value)
+    }
+
+    /// Remove a possibility from a cell, returning true if it was there
+    pub fn eliminate(&mut self, grid: &mut SGrid, row: usize, col: usize, value: u8) -> bool {
+        if grid.remove(row, col, value) {
+            self.eliminations.push((row, col, value));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Cut a cell of a house down to the given possibilities, as
+    /// `SGrid::alter_house` does, returning true if any were removed
+    pub fn restrict(&mut self, grid: &mut SGrid, house: usize, cell: usize, values: SCell) -> bool {
+        let current = grid.house_cell(house, cell);
+        if !grid.alter_house(house, cell, current.intersect(&values)) {
+            return false;
+        }
+        let (row, col) = grid.house_cell_to_row_col(house, cell);
+        for value in current.values().filter(|&value| !values.has(value)) {
+            self.eliminations.push((row, col, value));
+        }
+        true
+    }
+
+    /// Remove every one of the given possibilities from a cell, returning
+    /// true if any were there
+    pub fn eliminate_all(
+        &mut self,
+        grid: &mut SGrid,
+        row: usize,
+        col: usize,
+        values: SCell,
+    ) -> bool {
+        let mut acted = false;
+        for value in values.values() {
+            acted |= self.eliminate(grid, row, col, value);
+        }
+        acted
+    }
+}
+
+/// Write a list of cells, such as "r1c1 r1c5"
+fn write_cells(f: &mut fmt::Formatter<'_>, cells: &[(usize, usize)]) -> fmt::Result {
+    for (n, (row, col)) in cells.iter().enumerate() {
+        if n != 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "r{}c{}", row + 1, col + 1)?;
+    }
+    Ok(())
+}
+
+/// A step is shown as, for example,
+/// "x-wing: 5 in r2c3 r2c7 r6c3 r6c7 => r4c3<>5, r9c7<>5"
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.technique)?;
+        if !self.digits.is_empty() {
+            write!(f, " ")?;
+            for digit in &self.digits {
+                write!(f, "{}", digit)?;
+            }
+        }
+        if !self.cells.is_empty() {
+            write!(f, "{}", if self.digits.is_empty() { " " } else { " in " })?;
+            write_cells(f, &self.cells)?;
+        }
+        write!(f, " =>")?;
+        let placements = self
+            .placements
+            .iter()
+            .map(|(row, col, value)| (row, col, "=", value));
+        let eliminations = self
+            .eliminations
+            .iter()
+            .map(|(row, col, value)| (row, col, "<>", value));
+        for (n, (row, col, op, value)) in placements.chain(eliminations).enumerate() {
+            let sep = if n == 0 { " " } else { ", " };
+            write!(f, "{}r{}c{}{}{}", sep, row + 1, col + 1, op, value)?;
+        }
+        Ok(())
+    }
+}
@@ -7,17 +7,21 @@
 //! returns Stuck then solving has failed and the grid is considered
 //! insoluable.
 
+use super::backtrack::backtrack;
+use super::chain::{Chain, Conclusion, LinkGraph, LinkKinds};
+use super::step::Step;
+use super::Ruleset;
 use super::SCell;
 use super::SGrid;
 use super::SResult;
 
 use log::debug;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 pub enum SolveStepResult {
     Stuck,
-    Acted,
+    Acted(Step),
     Finished,
     Failed(SResult),
 }
@@ -27,6 +31,16 @@ use SolveStepResult::*;
 pub trait Technique {
     fn name(&self) -> &'static str;
 
+    /// How hard the technique is for a person to spot, on the scale used
+    /// by Sudoku Explainer.  Patterns Explainer does not know are rated
+    /// as the chains it would need to find the same thing.
+    fn difficulty(&self) -> f32;
+
+    /// Whether the technique is only valid on grids with a single solution
+    fn assumes_unique(&self) -> bool {
+        false
+    }
+
     fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
         match grid.done() {
             SResult::Finished => Finished,
@@ -50,6 +64,10 @@ impl Technique for NakedSingle {
         "naked single"
     }
 
+    fn difficulty(&self) -> f32 {
+        2.3
+    }
+
     fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
         for row in 0..9 {
             for col in 0..9 {
@@ -59,8 +77,11 @@ impl Technique for NakedSingle {
                         let mut values = cell.values();
                         if values.len() == 1 {
                             let val = values.next().unwrap();
-                            match grid.set_cell(row, col, val) {
-                                SResult::Continue | SResult::Finished => return Acted,
+                            let mut step = Step::new(self.name())
+                                .with_cells([(row, col)])
+                                .with_digits([val]);
+                            match step.place(grid, row, col, val) {
+                                SResult::Continue | SResult::Finished => return Acted(step),
                                 res => return Failed(res),
                             }
                         }
@@ -81,15 +102,47 @@ impl Technique for NakedSingle {
 /// possibilities but if no other cell in the house could have the value 2
 /// then this is a hidden single.  To act on a hidden single we simply
 /// replace all possibilities in the cell with the fixed hidden single.
-pub struct HiddenSingle;
+///
+/// Sudoku Explainer rates hidden singles in a box as easier than those in
+/// a row or column, so they can be looked for on their own.
+pub struct HiddenSingle {
+    boxes: bool,
+}
+
+impl HiddenSingle {
+    pub fn in_boxes() -> Self {
+        Self { boxes: true }
+    }
+
+    pub fn in_any_house() -> Self {
+        Self { boxes: false }
+    }
+}
 
 impl Technique for HiddenSingle {
     fn name(&self) -> &'static str {
-        "hidden single"
+        if self.boxes {
+            "hidden single in box"
+        } else {
+            "hidden single"
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        if self.boxes {
+            1.2
+        } else {
+            1.5
+        }
     }
 
     fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
-        for house in 0..27 {
+        let houses = if self.boxes {
+            grid.rules().boxes()
+        } else {
+            0..grid.rules().houses().len()
+        };
+        for house in houses {
             let content = grid.house(house);
             let mut found = HashMap::new();
             for (n, cell) in content.iter().enumerate() {
@@ -108,8 +161,14 @@ impl Technique for HiddenSingle {
                         let cell = s.iter().copied().next().unwrap();
                         debug!("Cell {} in house {} is {:?}", cell, house, content[cell]);
                         debug!("Trying to isolate it down to {}", value);
-                        grid.set_house(house, cell, value);
-                        return Acted;
+                        let (row, col) = grid.house_cell_to_row_col(house, cell);
+                        let mut step = Step::new(self.name())
+                            .with_cells([(row, col)])
+                            .with_digits([value]);
+                        return match step.place(grid, row, col, value) {
+                            SResult::Continue | SResult::Finished => Acted(step),
+                            res => Failed(res),
+                        };
                     }
                 }
             }
@@ -118,121 +177,242 @@ impl Technique for HiddenSingle {
     }
 }
 
-/// The naked pair technique
+fn subset_name(size: usize) -> &'static str {
+    match size {
+        2 => "pair",
+        3 => "triple",
+        4 => "quad",
+        _ => unreachable!(),
+    }
+}
+
+/// The naked subset techniques (pairs, triples and quads)
 ///
-/// A naked pair is where two unfixed cells in a house have the
-/// same two possibilities.  Where you have a naked pair you can
-/// then eliminate those two possibilities from any other cell in
-/// that house.
-pub struct NakedPair;
+/// A naked subset is where N unfixed cells in a house have, between
+/// them, only N possibilities.  Those N values must be placed in those
+/// N cells and so we can eliminate them from any other cell in that
+/// house.  A naked pair is two cells which have the same two
+/// possibilities; a naked triple need not have all three values in
+/// every cell, so long as there are only three values in total.
+pub struct NakedSubset {
+    size: usize,
+}
 
-impl Technique for NakedPair {
+impl NakedSubset {
+    pub fn new(size: usize) -> Self {
+        assert!((2..=4).contains(&size), "Subsets must be of size 2 to 4");
+        Self { size }
+    }
+}
+
+impl Technique for NakedSubset {
     fn name(&self) -> &'static str {
-        "naked pair"
+        match self.size {
+            2 => "naked pair",
+            3 => "naked triple",
+            4 => "naked quad",
+            _ => unreachable!(),
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        match self.size {
+            2 => 3.0,
+            3 => 3.6,
+            4 => 5.0,
+            _ => unreachable!(),
+        }
     }
 
     fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
-        for house in 0..27 {
+        for house in 0..grid.rules().houses().len() {
             let cells = grid.house(house);
-            for a in 0..8 {
-                if cells[a].possibilities() != 2 {
+            let candidates = (0..9)
+                .filter(|&n| (2..=self.size).contains(&cells[n].possibilities()))
+                .collect::<Vec<_>>();
+            for subset in combinations(&candidates, self.size) {
+                let values =
+                    subset
+                        .iter()
+                        .fold(SCell::Possible(0), |acc, &n| match (acc, cells[n]) {
+                            (SCell::Possible(a), SCell::Possible(b)) => SCell::Possible(a | b),
+                            _ => unreachable!(),
+                        });
+                if values.possibilities() != self.size {
                     continue;
                 }
-                for b in (a + 1)..9 {
-                    if cells[a] == cells[b] {
-                        // This is a naked pair, but can we do anything?
-                        debug!(
-                            "Found a naked pair of {:?} in house {} cells {} and {}",
-                            cells[a], house, a, b
-                        );
-                        let mut changed = false;
-                        for other in 0..9 {
-                            if other == a || other == b {
-                                continue;
-                            }
-                            let this_changed =
-                                grid.house_cell_mut(house, other).remove_all(cells[a]);
-                            if this_changed {
-                                debug!("We altered cell {} in the house", other);
-                            }
-                            changed |= this_changed;
-                        }
-                        if changed {
-                            debug!("We changed some cells as a result");
-                            return Acted;
-                        }
+                // This is a naked subset, but can we do anything?
+                debug!(
+                    "Found a naked {} of {:?} in house {} cells {:?}",
+                    subset_name(self.size),
+                    values,
+                    house,
+                    subset
+                );
+                let mut step = Step::new(self.name());
+                for other in 0..9 {
+                    if subset.contains(&other) {
+                        continue;
+                    }
+                    let (row, col) = grid.house_cell_to_row_col(house, other);
+                    if step.eliminate_all(grid, row, col, values) {
+                        debug!("We altered cell {} in the house", other);
                     }
                 }
+                if step.acted() {
+                    debug!("We changed some cells as a result");
+                    let cells = subset.iter().map(|&n| grid.house_cell_to_row_col(house, n));
+                    return Acted(step.with_cells(cells).with_digits(values.values()));
+                }
             }
         }
         Stuck
     }
 }
 
-/// The hidden pair technique.
+/// The hidden subset techniques (pairs, triples and quads)
 ///
-/// Hidden pair is a technique where you look in a house for two cells
-/// which, between them, are the only two cells which are possibly
-/// a given number.  Having found them, you can eliminate any other
-/// possiblities in those cells since those cells are limited to the
-/// given hidden pair.  This transforms the hidden pair into a naked
-/// pair, but that won't have any effect in *that* house (it may in
-/// an overlapping house).
-struct HiddenPair;
+/// A hidden subset is where, in a house, there are N values which
+/// between them can only be placed in N cells.  Having found them,
+/// you can eliminate any other possiblities in those cells since
+/// those cells are limited to the given hidden subset.  This
+/// transforms the hidden subset into a naked subset, but that won't
+/// have any effect in *that* house (it may in an overlapping house).
+pub struct HiddenSubset {
+    size: usize,
+}
+
+impl HiddenSubset {
+    pub fn new(size: usize) -> Self {
+        assert!((2..=4).contains(&size), "Subsets must be of size 2 to 4");
+        Self { size }
+    }
+}
 
-impl Technique for HiddenPair {
+impl Technique for HiddenSubset {
     fn name(&self) -> &'static str {
-        "HiddenPair"
+        match self.size {
+            2 => "hidden pair",
+            3 => "hidden triple",
+            4 => "hidden quad",
+            _ => unreachable!(),
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        match self.size {
+            2 => 3.4,
+            3 => 4.0,
+            4 => 5.4,
+            _ => unreachable!(),
+        }
     }
 
     fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
-        for house in 0..27 {
-            let content = grid.house(house);
-            let mut found = HashMap::new();
-            // First up, iterate the cells in the house and map from cell value
-            // to set of cells in the house which contain that value.
-            for (n, cell) in content.iter().enumerate() {
-                match cell {
-                    SCell::Fixed(_) => {}
-                    SCell::Possible(_) => {
-                        for value in cell.values() {
-                            found.entry(value).or_insert_with(HashSet::new).insert(n);
-                        }
-                    }
-                };
+        for house in 0..grid.rules().houses().len() {
+            // Map from each value to the set of cells in the house which
+            // could contain that value, ignoring values already placed.
+            let found = (1..=9)
+                .map(|value| (value, house_positions(grid, house, value)))
+                .filter(|(_, mask)| (2..=self.size).contains(&(mask.count_ones() as usize)))
+                .collect::<Vec<_>>();
+            for subset in combinations(&found, self.size) {
+                let cells = subset.iter().fold(0u16, |acc, (_, mask)| acc | mask);
+                if cells.count_ones() as usize != self.size {
+                    continue;
+                }
+                let values = subset
+                    .iter()
+                    .fold(0u16, |acc, (value, _)| acc | (1 << value));
+                debug!(
+                    "Found a hidden {} of {:?} in house {} cells {:09b}",
+                    subset_name(self.size),
+                    SCell::Possible(values),
+                    house,
+                    cells
+                );
+                let mut step = Step::new(self.name());
+                for n in (0..9).filter(|n| (cells & (1 << n)) != 0) {
+                    step.restrict(grid, house, n, SCell::Possible(values));
+                }
+                if step.acted() {
+                    let subset = (0..9)
+                        .filter(|n| (cells & (1 << n)) != 0)
+                        .map(|n| grid.house_cell_to_row_col(house, n));
+                    debug!("This resulted in an action");
+                    return Acted(
+                        step.with_cells(subset)
+                            .with_digits(SCell::Possible(values).values()),
+                    );
+                }
             }
-            // Now we're looking for *pairs* of values present in the same two cells
-            for a in 0..8 {
-                if found.get(&a).map(HashSet::len).unwrap_or(0) == 2 {
-                    for b in a + 1..9 {
-                        if found.get(&a) == found.get(&b) {
-                            let mut hs = found.get(&a).unwrap().iter();
-                            let c1 = *hs.next().unwrap();
-                            let c2 = *hs.next().unwrap();
-                            debug!(
-                                "Found a {}/{} pair in cells {},{} of house {}",
-                                a, b, c1, c2, house
-                            );
-                            let ncell = content[c1].intersect(&content[c2]);
-                            let mut changed = grid.alter_house(house, c1, ncell);
-                            changed |= grid.alter_house(house, c2, ncell);
-                            if changed {
-                                debug!("This resulted in an action");
-                                return Acted;
-                            }
-                        }
+        }
+        Stuck
+    }
+}
+
+/// Shared implementation of the locked candidate techniques
+///
+/// For each of the given houses, and each value, if every cell in the
+/// house which could be that value is also in one particular overlapping
+/// house, then the value must be placed in that overlap and so can be
+/// removed from the rest of the overlapping house.
+fn locked_candidates<I>(grid: &mut SGrid, name: &'static str, houses: I) -> SolveStepResult
+where
+    I: IntoIterator<Item = usize>,
+{
+    let rules = grid.rules();
+    for house in houses {
+        for value in 1..=9 {
+            let found_in_house = (0..9)
+                .filter(|&cell| {
+                    matches!(grid.house_cell(house, cell), SCell::Possible(_))
+                        && grid.house_cell(house, cell).has(value)
+                })
+                .map(|cell| grid.house_cell_to_row_col(house, cell))
+                .collect::<BTreeSet<_>>();
+            if found_in_house.len() < 2 {
+                // No point looking at overlaps, there's fewer than 2 so not "pointing"
+                continue;
+            }
+            for &other in rules.overlapping_houses(house) {
+                let other_cells = (0..9)
+                    .map(|cell| grid.house_cell_to_row_col(other, cell))
+                    .collect::<Vec<_>>();
+                if !found_in_house.iter().all(|pos| other_cells.contains(pos)) {
+                    continue;
+                }
+                debug!(
+                    "Found value {} in house {} in {} cells, all within house {}",
+                    value,
+                    house,
+                    found_in_house.len(),
+                    other
+                );
+                let mut step = Step::new(name);
+                for (row, col) in other_cells {
+                    if found_in_house.contains(&(row, col)) {
+                        // Skip a cell in the pointer
+                        continue;
+                    }
+                    if step.eliminate(grid, row, col, value) {
+                        debug!("Removed {} from row {} col {}", value, row, col);
                     }
                 }
+                if step.acted() {
+                    debug!("This did something");
+                    return Acted(step.with_cells(found_in_house).with_digits([value]));
+                }
             }
         }
-        Stuck
     }
+    Stuck
 }
 
 /// The pointing technique
 ///
-/// When all the cells in a given house which could be a particular
-/// value are also in another house, any other cells in that other house
+/// When all the cells in a box which could be a particular value are
+/// also in a single row or column, any other cells in that row or column
 /// which could be that value should have it removed from them.
 pub struct Pointing;
 
@@ -241,64 +421,166 @@ impl Technique for Pointing {
         "pointing"
     }
 
+    fn difficulty(&self) -> f32 {
+        2.6
+    }
+
     fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
-        for house in 0..27 {
-            for value in 1..=9 {
-                let mut found_in_house = HashSet::new();
-                for cell in 0..9 {
-                    if grid.house_cell(house, cell).values().any(|v| v == value) {
-                        let (row, col) = SGrid::house_cell_to_row_col(house, cell);
-                        found_in_house.insert((row, col));
-                    }
-                }
-                if found_in_house.len() < 2 {
-                    // No point looking at overlaps, there's fewer than 2 so not "pointing"
-                    continue;
-                }
+        let boxes = grid.rules().boxes();
+        locked_candidates(grid, self.name(), boxes)
+    }
+}
 
-                let mut sets = found_in_house.iter().copied().map(|(row, col)| {
-                    debug!(
-                        "Considering row {} col {} {:?}",
-                        row,
-                        col,
-                        grid.cell(row, col)
-                    );
-                    grid.rules()
-                        .sees(row, col)
-                        .iter()
-                        .copied()
-                        .collect::<HashSet<_>>()
-                });
-                let first_set = sets.next().unwrap();
-                println!("The first set is: {:?}", first_set);
-                let intersection = sets.fold(first_set, |s1, s2| {
-                    let isect = s1.intersection(&s2).copied().collect();
-                    debug!("Intersecting with {:?} produces {:?}", s2, isect);
-                    isect
-                });
-                // Every cell in intersection is seen by all cells pointing in the house
-                let mut acted = false;
-                debug!(
-                    "Found value {} in house {} in {} cells, pointing at {:?} cells",
-                    value,
-                    house,
-                    found_in_house.len(),
-                    intersection
-                );
-                for (row, col) in intersection {
-                    debug!("Considering row {} col {}", row, col);
-                    if found_in_house.contains(&(row, col)) {
-                        // Skip a cell in the pointer
+/// The box/line reduction technique (also known as claiming)
+///
+/// When all the cells in a row or column which could be a particular
+/// value are also in a single box, any other cells in that box which
+/// could be that value should have it removed from them.  Any extra
+/// houses, such as the diagonals, are treated in the same way as lines.
+pub struct BoxLineReduction;
+
+impl Technique for BoxLineReduction {
+    fn name(&self) -> &'static str {
+        "box/line reduction"
+    }
+
+    fn difficulty(&self) -> f32 {
+        2.8
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let houses = (0..rules.houses().len()).filter(|house| !rules.boxes().contains(house));
+        locked_candidates(grid, self.name(), houses)
+    }
+}
+
+/// Produce every combination of `size` elements of `items`, in order.
+fn combinations<T: Copy>(items: &[T], size: usize) -> Vec<Vec<T>> {
+    fn recurse<T: Copy>(items: &[T], size: usize, current: &mut Vec<T>, ret: &mut Vec<Vec<T>>) {
+        if current.len() == size {
+            ret.push(current.clone());
+            return;
+        }
+        for (n, item) in items.iter().enumerate() {
+            if items.len() - n < size - current.len() {
+                break;
+            }
+            current.push(*item);
+            recurse(&items[n + 1..], size, current, ret);
+            current.pop();
+        }
+    }
+    let mut ret = Vec::new();
+    recurse(items, size, &mut Vec::with_capacity(size), &mut ret);
+    ret
+}
+
+/// Bitmask of the cells in the given house which could still be the
+/// given value.  Fixed cells are never included.
+fn house_positions(grid: &SGrid, house: usize, value: u8) -> u16 {
+    grid.house(house)
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| matches!(cell, SCell::Possible(_)) && cell.has(value))
+        .fold(0, |mask, (n, _)| mask | (1 << n))
+}
+
+/// The set of cells which are seen by every one of the given cells.
+fn common_peers(rules: &dyn Ruleset, cells: &[(usize, usize)]) -> HashSet<(usize, usize)> {
+    let mut sets = cells
+        .iter()
+        .map(|&(row, col)| rules.sees(row, col).iter().copied().collect::<HashSet<_>>());
+    let first_set = sets.next().unwrap_or_default();
+    sets.fold(first_set, |s1, s2| s1.intersection(&s2).copied().collect())
+}
+
+fn fish_name(size: usize) -> &'static str {
+    match size {
+        2 => "x-wing",
+        3 => "swordfish",
+        4 => "jellyfish",
+        _ => unreachable!(),
+    }
+}
+
+/// The basic fish techniques (X-Wing, Swordfish and Jellyfish)
+///
+/// If, for a given value, there are N rows in which that value can only
+/// be in cells which, between them, lie in only N columns, then those N
+/// columns must have the value placed somewhere in those N rows.  As such
+/// the value can be eliminated from every other cell in those columns.
+/// The same is true with rows and columns swapped.  A fish of size 2 is
+/// an X-Wing, size 3 is a Swordfish and size 4 is a Jellyfish.
+pub struct BasicFish {
+    size: usize,
+}
+
+impl BasicFish {
+    pub fn new(size: usize) -> Self {
+        assert!((2..=4).contains(&size), "Fish must be of size 2 to 4");
+        Self { size }
+    }
+}
+
+impl Technique for BasicFish {
+    fn name(&self) -> &'static str {
+        fish_name(self.size)
+    }
+
+    fn difficulty(&self) -> f32 {
+        match self.size {
+            2 => 3.2,
+            3 => 3.8,
+            4 => 5.2,
+            _ => unreachable!(),
+        }
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        // Base sets of rows covered by columns, then base sets of columns
+        // covered by rows.  In either case the index of a cell within a
+        // base house is the number of the cover house it lies in.
+        let rules = grid.rules();
+        let houses = rules.houses();
+        let (rows, cols) = (rules.rows().start, rules.cols().start);
+        for &(base, cover) in &[(rows, cols), (cols, rows)] {
+            for value in 1..=9 {
+                let lines = (0..9)
+                    .map(|line| (line, house_positions(grid, base + line, value)))
+                    .filter(|(_, mask)| (2..=self.size).contains(&(mask.count_ones() as usize)))
+                    .collect::<Vec<_>>();
+                for fish in combinations(&lines, self.size) {
+                    let covers = fish.iter().fold(0u16, |acc, (_, mask)| acc | mask);
+                    if covers.count_ones() as usize != self.size {
                         continue;
                     }
-                    debug!("It contains {:?}", grid.cell(row, col));
-                    acted |= grid.cell_mut(row, col).remove(value);
-                    debug!("It now contains {:?}", grid.cell(row, col));
-                    debug!("Acted = {}", acted);
-                }
-                if acted {
-                    debug!("This did something");
-                    return Acted;
+                    let mut step = Step::new(self.name());
+                    for cover_line in (0..9).filter(|n| (covers & (1 << n)) != 0) {
+                        for cell in 0..9 {
+                            if fish.iter().any(|&(line, _)| line == cell) {
+                                continue;
+                            }
+                            let (row, col) = grid.house_cell_to_row_col(cover + cover_line, cell);
+                            if step.eliminate(grid, row, col, value) {
+                                debug!("Removed {} from row {} col {}", value, row, col);
+                            }
+                        }
+                    }
+                    if step.acted() {
+                        debug!(
+                            "Found a {} on {} in houses {:?}",
+                            self.name(),
+                            value,
+                            fish.iter().map(|(line, _)| base + line).collect::<Vec<_>>()
+                        );
+                        let cells = fish.iter().flat_map(|&(line, mask)| {
+                            (0..9)
+                                .filter(move |n| (mask & (1 << n)) != 0)
+                                .map(move |n| houses[base + line][n])
+                        });
+                        return Acted(step.with_cells(cells).with_digits([value]));
+                    }
                 }
             }
         }
@@ -306,89 +588,3386 @@ impl Technique for Pointing {
     }
 }
 
-pub struct SolverSet {
-    techniques: Vec<Box<dyn Technique>>,
-    actions: Vec<usize>,
-    defers: Vec<usize>,
+/// The finned and sashimi fish techniques
+///
+/// A finned fish is a basic fish (see [`BasicFish`]) where some of the
+/// base houses also have the value in cells outside of the cover houses.
+/// These extra cells are the fins.  Either one of the fins is the value,
+/// or the basic fish holds; so any cell in the cover houses (and outside
+/// the base houses) which can see every fin can have the value removed.
+/// In a normal sudoku this means the fins all share a box with the cells
+/// which are eliminated, but we rely only on the ruleset's idea of which
+/// cells see which.
+///
+/// A sashimi fish is a finned fish where, were the fins removed, one of
+/// the base houses would have only a single cell left for the value.
+pub struct FinnedFish {
+    size: usize,
+    sashimi: bool,
 }
 
-impl SolverSet {
-    pub fn new() -> SolverSet {
+impl FinnedFish {
+    pub fn finned(size: usize) -> Self {
+        assert!((2..=4).contains(&size), "Fish must be of size 2 to 4");
         Self {
-            techniques: Vec::new(),
-            actions: Vec::new(),
-            defers: Vec::new(),
+            size,
+            sashimi: false,
         }
     }
 
-    pub fn add_technique<T>(&mut self, t: T)
-    where
-        T: Technique + 'static,
-    {
-        self.techniques.push(Box::new(t));
-        self.actions.push(0);
-        self.defers.push(0);
+    pub fn sashimi(size: usize) -> Self {
+        assert!((2..=4).contains(&size), "Fish must be of size 2 to 4");
+        Self {
+            size,
+            sashimi: true,
+        }
     }
+}
 
-    pub fn solve_grid(&mut self, grid: &mut SGrid) -> SolveStepResult {
-        let mut tnum = 0;
-        'outer: loop {
-            if let SResult::Finished = grid.done() {
-                break Finished;
-            }
-            if tnum == self.techniques.len() {
-                break Stuck;
-            }
-            debug!("Trying {}", self.techniques[tnum].name());
-            match self.techniques[tnum].step(grid) {
-                Stuck => {
-                    debug!("{} is stuck", self.techniques[tnum].name());
-                    self.defers[tnum] += 1;
-                    tnum += 1;
-                }
-                Acted => {
-                    debug!("{} acted", self.techniques[tnum].name());
-                    self.actions[tnum] += 1;
-                    tnum = 0;
-                }
-                res => {
-                    break res;
+impl Technique for FinnedFish {
+    fn name(&self) -> &'static str {
+        match (self.sashimi, self.size) {
+            (false, 2) => "finned x-wing",
+            (false, 3) => "finned swordfish",
+            (false, 4) => "finned jellyfish",
+            (true, 2) => "sashimi x-wing",
+            (true, 3) => "sashimi swordfish",
+            (true, 4) => "sashimi jellyfish",
+            _ => unreachable!(),
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        match (self.sashimi, self.size) {
+            (false, 2) => 6.5,
+            (false, 3) => 6.7,
+            (false, 4) => 6.9,
+            (true, 2) => 6.6,
+            (true, 3) => 6.8,
+            (true, 4) => 7.0,
+            _ => unreachable!(),
+        }
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let houses = rules.houses();
+        let peers = peer_masks(&*rules);
+        let line_masks = houses
+            .iter()
+            .map(|house| {
+                house
+                    .iter()
+                    .fold(0u128, |acc, &(row, col)| acc | 1 << (row * 9 + col))
+            })
+            .collect::<Vec<_>>();
+        let (rows, cols) = (rules.rows().start, rules.cols().start);
+        // The fins in a base house must all see a cell outside it, so
+        // there can be no more of them than any cell sees of a line it is
+        // not in (three, in a normal sudoku).
+        let max_fins = rules
+            .rows()
+            .chain(rules.cols())
+            .flat_map(|line| {
+                let (peers, line_masks) = (&peers, &line_masks);
+                mask_cells(!line_masks[line] & ((1 << 81) - 1))
+                    .map(move |(row, col)| (peers[row * 9 + col] & line_masks[line]).count_ones())
+            })
+            .max()
+            .unwrap_or(0) as usize;
+        for &(base, cover) in &[(rows, cols), (cols, rows)] {
+            for value in 1..=9 {
+                let lines = (0..9)
+                    .map(|line| (line, house_positions(grid, base + line, value)))
+                    .filter(|(_, mask)| {
+                        *mask != 0 && mask.count_ones() as usize <= self.size + max_fins
+                    })
+                    .collect::<Vec<_>>();
+                let candidates = (0..81)
+                    .filter(|&pos| {
+                        let cell = grid.cell(pos / 9, pos % 9);
+                        matches!(cell, SCell::Possible(_)) && cell.has(value)
+                    })
+                    .fold(0u128, |acc, pos| acc | 1 << pos);
+                for fish in combinations(&lines, self.size) {
+                    let all = fish.iter().fold(0u16, |acc, (_, mask)| acc | mask);
+                    if all.count_ones() as usize <= self.size {
+                        // Either a basic fish, or nothing at all
+                        continue;
+                    }
+                    let bases = fish
+                        .iter()
+                        .fold(0u128, |acc, &(line, _)| acc | line_masks[base + line]);
+                    let all = (0..9).filter(|n| (all & (1 << n)) != 0).collect::<Vec<_>>();
+                    for covers in combinations(&all, self.size) {
+                        let covers = covers.iter().fold(0u16, |acc, n| acc | (1 << n));
+                        let in_cover = fish
+                            .iter()
+                            .map(|(_, mask)| (mask & covers).count_ones())
+                            .collect::<Vec<_>>();
+                        if in_cover.contains(&0) {
+                            continue;
+                        }
+                        if self.sashimi != in_cover.contains(&1) {
+                            continue;
+                        }
+                        // The cells in the cover houses, outside the base
+                        // houses, which could be the value and see every fin
+                        let mut targets = (0..9)
+                            .filter(|n| (covers & (1 << n)) != 0)
+                            .fold(0u128, |acc, n| acc | line_masks[cover + n])
+                            & !bases
+                            & candidates;
+                        let mut fins = Vec::new();
+                        for &(line, mask) in &fish {
+                            for n in (0..9).filter(|n| (mask & !covers & (1 << n)) != 0) {
+                                let (row, col) = houses[base + line][n];
+                                targets &= peers[row * 9 + col];
+                                fins.push((row, col));
+                            }
+                        }
+                        if targets == 0 {
+                            continue;
+                        }
+                        let mut step = Step::new(self.name());
+                        for (row, col) in mask_cells(targets) {
+                            step.eliminate(grid, row, col, value);
+                        }
+                        debug!(
+                            "Found a {} on {} in houses {:?} with fins {:?}",
+                            self.name(),
+                            value,
+                            fish.iter().map(|(line, _)| base + line).collect::<Vec<_>>(),
+                            fins
+                        );
+                        let cells = fish.iter().flat_map(|&(line, mask)| {
+                            (0..9)
+                                .filter(move |n| (mask & (1 << n)) != 0)
+                                .map(move |n| houses[base + line][n])
+                        });
+                        return Acted(step.with_cells(cells).with_digits([value]));
+                    }
                 }
             }
-            for row in 0..9 {
-                for col in 0..9 {
-                    if grid.cell(row, col).values().len() == 0 {
-                        debug!("Well, that broke the grid!");
-                        break 'outer Stuck;
+        }
+        Stuck
+    }
+}
+
+/// The XY-Wing technique
+///
+/// An XY-Wing is formed of three cells which each have two possibilities.
+/// The pivot cell has possibilities X and Y.  It sees two pincer cells,
+/// one with possibilities X and Z, the other with Y and Z.  Whichever
+/// value the pivot takes, one of the pincers must be Z, so any cell which
+/// sees both pincers cannot be Z.
+pub struct XYWing;
+
+impl Technique for XYWing {
+    fn name(&self) -> &'static str {
+        "xy-wing"
+    }
+
+    fn difficulty(&self) -> f32 {
+        4.2
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        for prow in 0..9 {
+            for pcol in 0..9 {
+                let pivot = grid.cell(prow, pcol);
+                if pivot.possibilities() != 2 {
+                    continue;
+                }
+                let pincers = rules
+                    .sees(prow, pcol)
+                    .iter()
+                    .copied()
+                    .filter(|&(row, col)| {
+                        let cell = grid.cell(row, col);
+                        cell.possibilities() == 2
+                            && cell != pivot
+                            && cell.intersect(&pivot).possibilities() == 1
+                    })
+                    .collect::<Vec<_>>();
+                for pair in combinations(&pincers, 2) {
+                    let (a, b) = (
+                        grid.cell(pair[0].0, pair[0].1),
+                        grid.cell(pair[1].0, pair[1].1),
+                    );
+                    let z = a.intersect(&b);
+                    if a == b || z.possibilities() != 1 || pivot.has(z.values().next().unwrap()) {
+                        continue;
+                    }
+                    let z = z.values().next().unwrap();
+                    let mut step = Step::new(self.name());
+                    for (row, col) in common_peers(&*rules, &pair) {
+                        if step.eliminate(grid, row, col, z) {
+                            debug!("Removed {} from row {} col {}", z, row, col);
+                        }
+                    }
+                    if step.acted() {
+                        debug!(
+                            "Found an xy-wing pivoting on row {} col {} with pincers {:?}",
+                            prow, pcol, pair
+                        );
+                        let cells = [(prow, pcol), pair[0], pair[1]];
+                        let digits = pivot.values().chain([z]);
+                        return Acted(step.with_cells(cells).with_digits(digits));
                     }
                 }
             }
         }
+        Stuck
     }
+}
 
-    pub fn dump_actions(&self) {
-        for ((technique, defer), action) in self
-            .techniques
-            .iter()
-            .zip(self.defers.iter())
-            .zip(self.actions.iter())
-        {
-            println!(
-                "{} deferred {} times and acted {} times",
-                technique.name(),
-                defer,
-                action
-            );
-        }
+/// The XYZ-Wing technique
+///
+/// An XYZ-Wing is like an XY-Wing (see [`XYWing`]) except that the pivot
+/// cell also has Z as a possibility, having possibilities X, Y and Z.  In
+/// that case one of the three cells must be Z, so any cell which sees all
+/// three of them cannot be Z.
+pub struct XYZWing;
+
+impl Technique for XYZWing {
+    fn name(&self) -> &'static str {
+        "xyz-wing"
     }
 
-    pub fn full() -> SolverSet {
-        let mut ret = SolverSet::new();
+    fn difficulty(&self) -> f32 {
+        4.4
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        for prow in 0..9 {
+            for pcol in 0..9 {
+                let pivot = grid.cell(prow, pcol);
+                if pivot.possibilities() != 3 {
+                    continue;
+                }
+                let pincers = rules
+                    .sees(prow, pcol)
+                    .iter()
+                    .copied()
+                    .filter(|&(row, col)| {
+                        let cell = grid.cell(row, col);
+                        cell.possibilities() == 2 && cell.intersect(&pivot) == cell
+                    })
+                    .collect::<Vec<_>>();
+                for pair in combinations(&pincers, 2) {
+                    let (a, b) = (
+                        grid.cell(pair[0].0, pair[0].1),
+                        grid.cell(pair[1].0, pair[1].1),
+                    );
+                    let z = a.intersect(&b);
+                    if a == b || z.possibilities() != 1 {
+                        continue;
+                    }
+                    let z = z.values().next().unwrap();
+                    let mut step = Step::new(self.name());
+                    for (row, col) in common_peers(&*rules, &[(prow, pcol), pair[0], pair[1]]) {
+                        if step.eliminate(grid, row, col, z) {
+                            debug!("Removed {} from row {} col {}", z, row, col);
+                        }
+                    }
+                    if step.acted() {
+                        debug!(
+                            "Found an xyz-wing pivoting on row {} col {} with pincers {:?}",
+                            prow, pcol, pair
+                        );
+                        let cells = [(prow, pcol), pair[0], pair[1]];
+                        return Acted(step.with_cells(cells).with_digits(pivot.values()));
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// A strong link for a value is a house in which the value can be in
+/// exactly two cells.  One of the two ends must be the value.
+#[derive(Copy, Clone, Debug)]
+struct StrongLink {
+    house: usize,
+    ends: [(usize, usize); 2],
+}
+
+/// Find all the strong links for the given value across every house.
+fn strong_links(grid: &SGrid, value: u8) -> Vec<StrongLink> {
+    (0..grid.rules().houses().len())
+        .filter_map(|house| {
+            let mask = house_positions(grid, house, value);
+            if mask.count_ones() != 2 {
+                return None;
+            }
+            let mut cells = (0..9)
+                .filter(|n| (mask & (1 << n)) != 0)
+                .map(|n| grid.house_cell_to_row_col(house, n));
+            Some(StrongLink {
+                house,
+                ends: [cells.next().unwrap(), cells.next().unwrap()],
+            })
+        })
+        .collect()
+}
+
+fn sees(rules: &dyn Ruleset, a: (usize, usize), b: (usize, usize)) -> bool {
+    rules.sees(a.0, a.1).contains(&b)
+}
+
+fn house_contains(rules: &dyn Ruleset, house: usize, pos: (usize, usize)) -> bool {
+    rules.houses()[house].contains(&pos)
+}
+
+/// Shared implementation of the two strong link techniques
+///
+/// Given two strong links for a value, A=B and C=D, where B sees C, then
+/// at most one of B and C can be the value and so at least one of A and D
+/// must be.  Any cell which sees both A and D cannot be the value.  The
+/// `accept` function decides which shapes of link pair the technique
+/// covers, given the two links and the cells B and C.
+fn two_strong_links<F>(grid: &mut SGrid, name: &'static str, accept: F) -> SolveStepResult
+where
+    F: Fn(&StrongLink, &StrongLink, (usize, usize), (usize, usize)) -> bool,
+{
+    let rules = grid.rules();
+    for value in 1..=9 {
+        let links = strong_links(grid, value);
+        for (n, first) in links.iter().enumerate() {
+            for second in &links[n + 1..] {
+                for &(a, b) in &[(0, 1), (1, 0)] {
+                    for &(c, d) in &[(0, 1), (1, 0)] {
+                        let cells = [first.ends[a], first.ends[b], second.ends[c], second.ends[d]];
+                        if (0..4).any(|i| (i + 1..4).any(|j| cells[i] == cells[j])) {
+                            continue;
+                        }
+                        if !sees(&*rules, cells[1], cells[2])
+                            || !accept(first, second, cells[1], cells[2])
+                        {
+                            continue;
+                        }
+                        let mut step = Step::new(name);
+                        for (row, col) in common_peers(&*rules, &[cells[0], cells[3]]) {
+                            if cells.contains(&(row, col)) {
+                                continue;
+                            }
+                            if step.eliminate(grid, row, col, value) {
+                                debug!("Removed {} from row {} col {}", value, row, col);
+                            }
+                        }
+                        if step.acted() {
+                            debug!("Found a {} on {} using {:?}", name, value, cells);
+                            return Acted(step.with_cells(cells).with_digits([value]));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Stuck
+}
+
+/// The skyscraper technique
+///
+/// A skyscraper is a pair of strong links in two rows (or two columns)
+/// where one end of each link lies in the same column (or row).  At
+/// least one of the other two ends must be the value.
+pub struct Skyscraper;
+
+impl Technique for Skyscraper {
+    fn name(&self) -> &'static str {
+        "skyscraper"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.6
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let (rows, cols) = (rules.rows(), rules.cols());
+        two_strong_links(grid, self.name(), |first, second, b, c| {
+            (rows.contains(&first.house) && rows.contains(&second.house) && b.1 == c.1)
+                || (cols.contains(&first.house) && cols.contains(&second.house) && b.0 == c.0)
+        })
+    }
+}
+
+/// The 2-string kite technique
+///
+/// A 2-string kite is a strong link in a row and a strong link in a
+/// column where one end of each link lies in the same box.  At least one
+/// of the other two ends must be the value.
+pub struct TwoStringKite;
+
+impl Technique for TwoStringKite {
+    fn name(&self) -> &'static str {
+        "2-string kite"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.6
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let (rows, cols) = (rules.rows(), rules.cols());
+        two_strong_links(grid, self.name(), |first, second, b, c| {
+            let (row, col) = if rows.contains(&first.house) {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            rows.contains(&row.house)
+                && cols.contains(&col.house)
+                && rules.boxes().any(|house| {
+                    house_contains(&*rules, house, b) && house_contains(&*rules, house, c)
+                })
+        })
+    }
+}
+
+/// The turbot fish technique
+///
+/// A turbot fish is any pair of strong links, in any kind of house, where
+/// one end of the first link sees one end of the second.  This covers
+/// everything the skyscraper and 2-string kite do, so it should be tried
+/// after them.
+pub struct TurbotFish;
+
+impl Technique for TurbotFish {
+    fn name(&self) -> &'static str {
+        "turbot fish"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.6
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        two_strong_links(grid, self.name(), |_, _, _, _| true)
+    }
+}
+
+/// The empty rectangle technique
+///
+/// If, in a box, every cell which could be a value lies in one row or
+/// one column of that box (forming a cross with an empty rectangle in
+/// the rest of the box), then the box's value is in that row or that
+/// column.  Given a strong link in a column outside the box, one end of
+/// which is in the cross's row, then the other end of the link being
+/// false forces the value into the cross's row, and so it cannot be in
+/// the cell where the cross's column meets the row of that other end.
+/// The same holds with rows and columns swapped.
+pub struct EmptyRectangle;
+
+impl Technique for EmptyRectangle {
+    fn name(&self) -> &'static str {
+        "empty rectangle"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.6
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        for value in 1..=9 {
+            let links = strong_links(grid, value);
+            for house in rules.boxes() {
+                let cells = (0..9)
+                    .map(|n| grid.house_cell_to_row_col(house, n))
+                    .collect::<Vec<_>>();
+                let mask = house_positions(grid, house, value);
+                let found = (0..9)
+                    .filter(|n| (mask & (1 << n)) != 0)
+                    .map(|n| cells[n])
+                    .collect::<Vec<_>>();
+                if found.len() < 2 {
+                    continue;
+                }
+                for &(row, col) in &cells {
+                    if !found.iter().all(|&(r, c)| r == row || c == col)
+                        || found.iter().all(|&(r, _)| r == row)
+                        || found.iter().all(|&(_, c)| c == col)
+                    {
+                        continue;
+                    }
+                    for link in &links {
+                        for &(a, b) in &[(0, 1), (1, 0)] {
+                            let (near, far) = (link.ends[a], link.ends[b]);
+                            let target = if rules.cols().contains(&link.house)
+                                && near.0 == row
+                                && !cells.iter().any(|&(_, c)| c == near.1)
+                                && !cells.iter().any(|&(r, _)| r == far.0)
+                            {
+                                (far.0, col)
+                            } else if rules.rows().contains(&link.house)
+                                && near.1 == col
+                                && !cells.iter().any(|&(r, _)| r == near.0)
+                                && !cells.iter().any(|&(_, c)| c == far.1)
+                            {
+                                (row, far.1)
+                            } else {
+                                continue;
+                            };
+                            let mut step = Step::new(self.name());
+                            if step.eliminate(grid, target.0, target.1, value) {
+                                debug!(
+                                    "Found an empty rectangle on {} in house {} crossing at row {} col {} with strong link {:?}",
+                                    value, house, row, col, link
+                                );
+                                debug!("Removed {} from row {} col {}", value, target.0, target.1);
+                                let cells = found.iter().copied().chain(link.ends);
+                                return Acted(step.with_cells(cells).with_digits([value]));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// The W-Wing technique
+///
+/// A W-Wing is a pair of cells which have the same two possibilities, X
+/// and Y, but which do not see each other.  If there is a strong link on
+/// X where one end sees one of the cells and the other end sees the other
+/// cell, then at least one of the cells must be Y.  Any cell which sees
+/// both cells cannot be Y.
+pub struct WWing;
+
+impl Technique for WWing {
+    fn name(&self) -> &'static str {
+        "w-wing"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.6
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let bivalues = (0..9)
+            .flat_map(|row| (0..9).map(move |col| (row, col)))
+            .filter(|&(row, col)| grid.cell(row, col).possibilities() == 2)
+            .collect::<Vec<_>>();
+        // The strong links for each value, indexed by value - 1
+        let links = (1..=9)
+            .map(|value| strong_links(grid, value))
+            .collect::<Vec<_>>();
+        for pair in combinations(&bivalues, 2) {
+            let (p, q) = (pair[0], pair[1]);
+            let cell = grid.cell(p.0, p.1);
+            if cell != grid.cell(q.0, q.1) || sees(&*rules, p, q) {
+                continue;
+            }
+            for x in cell.values() {
+                let y = cell.values().find(|&v| v != x).unwrap();
+                let linked = links[x as usize - 1].iter().find(|link| {
+                    let [a, b] = link.ends;
+                    !pair.contains(&a)
+                        && !pair.contains(&b)
+                        && ((sees(&*rules, a, p) && sees(&*rules, b, q))
+                            || (sees(&*rules, a, q) && sees(&*rules, b, p)))
+                });
+                if let Some(link) = linked {
+                    let mut step = Step::new(self.name());
+                    for (row, col) in common_peers(&*rules, &pair) {
+                        if step.eliminate(grid, row, col, y) {
+                            debug!("Removed {} from row {} col {}", y, row, col);
+                        }
+                    }
+                    if step.acted() {
+                        debug!("Found a w-wing on {:?} and {:?} linked by {:?}", p, q, link);
+                        let cells = [p, link.ends[0], link.ends[1], q];
+                        return Acted(step.with_cells(cells).with_digits([x, y]));
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// Build the clusters of conjugate pairs for a value
+///
+/// Every strong link for the value joins its two ends.  Each connected
+/// group of cells is a cluster, and is coloured with two colours such
+/// that the two ends of every link differ.  Exactly one of the colours
+/// in each cluster must be the value.
+fn colour_clusters(grid: &SGrid, value: u8) -> Vec<[Vec<(usize, usize)>; 2]> {
+    let mut links = BTreeMap::new();
+    for link in strong_links(grid, value) {
+        let [a, b] = link.ends;
+        links.entry(a).or_insert_with(Vec::new).push(b);
+        links.entry(b).or_insert_with(Vec::new).push(a);
+    }
+    let mut coloured = HashSet::new();
+    let mut clusters = Vec::new();
+    for &start in links.keys() {
+        if coloured.contains(&start) {
+            continue;
+        }
+        let mut cluster = [Vec::new(), Vec::new()];
+        let mut queue = VecDeque::new();
+        queue.push_back((start, 0));
+        coloured.insert(start);
+        while let Some((pos, colour)) = queue.pop_front() {
+            cluster[colour].push(pos);
+            for &next in &links[&pos] {
+                if coloured.insert(next) {
+                    queue.push_back((next, 1 - colour));
+                }
+            }
+        }
+        clusters.push(cluster);
+    }
+    clusters
+}
+
+/// Cells which could be the given value and which are not in any of the
+/// given colours.
+fn uncoloured_cells(grid: &SGrid, value: u8, colours: &[&[(usize, usize)]]) -> Vec<(usize, usize)> {
+    (0..9)
+        .flat_map(|row| (0..9).map(move |col| (row, col)))
+        .filter(|&(row, col)| {
+            matches!(grid.cell(row, col), SCell::Possible(_)) && grid.cell(row, col).has(value)
+        })
+        .filter(|pos| !colours.iter().any(|colour| colour.contains(pos)))
+        .collect()
+}
+
+fn sees_any(rules: &dyn Ruleset, pos: (usize, usize), cells: &[(usize, usize)]) -> bool {
+    cells.iter().any(|&other| sees(rules, pos, other))
+}
+
+/// The simple colouring technique
+///
+/// Using the clusters of conjugate pairs for a value (see
+/// [`colour_clusters`]), we can apply two rules.  If two cells of the
+/// same colour see each other then that colour cannot be the value and
+/// it is removed from every cell of that colour (a colour wrap).  If a
+/// cell outside the cluster sees cells of both colours then it cannot be
+/// the value since one of the colours must be (a colour trap).
+pub struct SimpleColouring;
+
+impl Technique for SimpleColouring {
+    fn name(&self) -> &'static str {
+        "simple colouring"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.5
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        for value in 1..=9 {
+            for cluster in colour_clusters(grid, value) {
+                for colour in &cluster {
+                    if combinations(colour, 2)
+                        .iter()
+                        .any(|pair| sees(&*rules, pair[0], pair[1]))
+                    {
+                        debug!("Colour wrap on {} in {:?}", value, colour);
+                        let mut step = Step::new(self.name());
+                        for &(row, col) in colour {
+                            step.eliminate(grid, row, col, value);
+                        }
+                        let cells = cluster.iter().flatten().copied();
+                        return Acted(step.with_cells(cells).with_digits([value]));
+                    }
+                }
+                let mut step = Step::new(self.name());
+                for (row, col) in uncoloured_cells(grid, value, &[&cluster[0], &cluster[1]]) {
+                    if sees_any(&*rules, (row, col), &cluster[0])
+                        && sees_any(&*rules, (row, col), &cluster[1])
+                        && step.eliminate(grid, row, col, value)
+                    {
+                        debug!("Removed {} from row {} col {}", value, row, col);
+                    }
+                }
+                if step.acted() {
+                    debug!("Colour trap on {} using {:?}", value, cluster);
+                    let cells = cluster.iter().flatten().copied();
+                    return Acted(step.with_cells(cells).with_digits([value]));
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// The multi-colouring technique
+///
+/// Multi-colouring considers two clusters of conjugate pairs for a value
+/// at once.  If a cell of colour A in the first cluster sees a cell of
+/// colour C in the second, then A and C cannot both be the value, so at
+/// least one of their opposite colours, B and D, must be.  Any cell
+/// which sees cells of both B and D cannot be the value.  Further, if A
+/// also sees a cell of colour D then, since one of C and D must be the
+/// value, A cannot be and the value is removed from every cell of A.
+pub struct MultiColouring;
+
+impl Technique for MultiColouring {
+    fn name(&self) -> &'static str {
+        "multi-colouring"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.8
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        for value in 1..=9 {
+            let clusters = colour_clusters(grid, value);
+            for (first, second) in clusters
+                .iter()
+                .flat_map(|first| clusters.iter().map(move |second| (first, second)))
+                .filter(|(first, second)| !std::ptr::eq(*first, *second))
+            {
+                for a in 0..2 {
+                    let linked = (0..2)
+                        .filter(|&c| {
+                            first[a]
+                                .iter()
+                                .any(|&pos| sees_any(&*rules, pos, &second[c]))
+                        })
+                        .collect::<Vec<_>>();
+                    if linked.len() == 2 {
+                        debug!("Multi-colour wrap on {} in {:?}", value, first[a]);
+                        let mut step = Step::new(self.name());
+                        for &(row, col) in &first[a] {
+                            step.eliminate(grid, row, col, value);
+                        }
+                        let cells = first.iter().chain(second.iter()).flatten().copied();
+                        return Acted(step.with_cells(cells).with_digits([value]));
+                    }
+                    for c in linked {
+                        let (b, d) = (&first[1 - a], &second[1 - c]);
+                        let mut step = Step::new(self.name());
+                        for (row, col) in uncoloured_cells(
+                            grid,
+                            value,
+                            &[&first[0], &first[1], &second[0], &second[1]],
+                        ) {
+                            if sees_any(&*rules, (row, col), b)
+                                && sees_any(&*rules, (row, col), d)
+                                && step.eliminate(grid, row, col, value)
+                            {
+                                debug!("Removed {} from row {} col {}", value, row, col);
+                            }
+                        }
+                        if step.acted() {
+                            debug!(
+                                "Multi-colour trap on {} using {:?} and {:?}",
+                                value, first, second
+                            );
+                            let cells = first.iter().chain(second.iter()).flatten().copied();
+                            return Acted(step.with_cells(cells).with_digits([value]));
+                        }
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// Apply the conclusion of a chain to the grid
+fn apply_chain(grid: &mut SGrid, name: &'static str, chain: Chain) -> SolveStepResult {
+    debug!("Found a {} {:?}", name, chain.nodes);
+    let mut step = Step::new(name)
+        .with_cells(
+            chain
+                .nodes
+                .iter()
+                .flat_map(|node| node.cells.iter().copied()),
+        )
+        .with_digits(chain.nodes.iter().map(|node| node.value));
+    match chain.conclusion {
+        Conclusion::Eliminate(eliminations) => {
+            for (row, col, value) in eliminations {
+                debug!("Removed {} from row {} col {}", value, row, col);
+                step.eliminate(grid, row, col, value);
+            }
+            Acted(step)
+        }
+        Conclusion::Place(row, col, value) => match step.place(grid, row, col, value) {
+            SResult::Continue | SResult::Finished => Acted(step),
+            res => Failed(res),
+        },
+    }
+}
+
+/// The X-Chain technique
+///
+/// An X-Chain is an alternating inference chain (see [`LinkGraph`]) for
+/// a single value, built only from conjugate pairs and cells which see
+/// each other.  One of the two ends of the chain must be the value, so
+/// any cell which sees both ends cannot be.  Skyscrapers, 2-string kites
+/// and turbot fish are all short X-Chains.
+pub struct XChain;
+
+impl Technique for XChain {
+    fn name(&self) -> &'static str {
+        "x-chain"
+    }
+
+    fn difficulty(&self) -> f32 {
+        6.6
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let chain = (1..=9)
+            .filter_map(|value| {
+                LinkGraph::new(grid, LinkKinds::single_value(value)).find_chain(grid)
+            })
+            .min_by_key(|chain| chain.nodes.len());
+        match chain {
+            Some(chain) => apply_chain(grid, self.name(), chain),
+            None => Stuck,
+        }
+    }
+}
+
+/// The XY-Chain technique
+///
+/// An XY-Chain is an alternating inference chain through bivalue cells,
+/// where each cell is linked to the next by a value they share.  If the
+/// first cell is not the value it shares with nothing before it, then
+/// the last cell must be the value it shares with nothing after it.  The
+/// XY-Wing is a short XY-Chain.
+pub struct XYChain;
+
+impl Technique for XYChain {
+    fn name(&self) -> &'static str {
+        "xy-chain"
+    }
+
+    fn difficulty(&self) -> f32 {
+        7.0
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        match LinkGraph::new(grid, LinkKinds::BIVALUE).find_chain(grid) {
+            Some(chain) => apply_chain(grid, self.name(), chain),
+            None => Stuck,
+        }
+    }
+}
+
+/// The alternating inference chain technique
+///
+/// A general alternating inference chain uses every kind of link, mixing
+/// conjugate pairs, bivalue cells and grouped nodes in box/line
+/// intersections.  One of the two ends of the chain must be true, so any
+/// candidate which is false if either end is true can be removed.
+pub struct AlternatingInferenceChain;
+
+impl Technique for AlternatingInferenceChain {
+    fn name(&self) -> &'static str {
+        "alternating inference chain"
+    }
+
+    fn difficulty(&self) -> f32 {
+        7.5
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        match LinkGraph::new(grid, LinkKinds::ALL).find_chain(grid) {
+            Some(chain) => apply_chain(grid, self.name(), chain),
+            None => Stuck,
+        }
+    }
+}
+
+/// The discontinuous nice loop technique
+///
+/// A nice loop is a chain which returns to where it started.  If assuming
+/// a candidate is true leads, by alternating links, back to it being false
+/// then it can be removed.  If assuming it false leads back to it being
+/// true then it can be placed.
+pub struct NiceLoop;
+
+impl Technique for NiceLoop {
+    fn name(&self) -> &'static str {
+        "discontinuous nice loop"
+    }
+
+    fn difficulty(&self) -> f32 {
+        7.5
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        match LinkGraph::new(grid, LinkKinds::ALL).find_loop() {
+            Some(chain) => apply_chain(grid, self.name(), chain),
+            None => Stuck,
+        }
+    }
+}
+
+/// A possible deadly pattern: four cells at the corners of a rectangle
+/// across two rows, two columns and two boxes, all of which could be
+/// either of two values.  If the grid has only one solution then these
+/// cells cannot end up as only those two values, since they could then
+/// be swapped.  Rulesets with extra constraints may forbid the swap, in
+/// which case the rectangle is not used (see [`swappable`]).
+///
+/// The corners are ordered such that 0 and 1 share a row, as do 2 and 3,
+/// and 0 and 2 share a column, as do 1 and 3.
+struct Rectangle {
+    corners: [(usize, usize); 4],
+    values: [u8; 2],
+}
+
+impl Rectangle {
+    fn pair(&self) -> SCell {
+        SCell::Possible((1 << self.values[0]) | (1 << self.values[1]))
+    }
+
+    /// The corners which have only the rectangle's two values
+    fn floor(&self, grid: &SGrid) -> Vec<usize> {
+        (0..4)
+            .filter(|&n| grid.cell(self.corners[n].0, self.corners[n].1) == self.pair())
+            .collect()
+    }
+
+    /// The corners which have more than the rectangle's two values
+    fn roof(&self, grid: &SGrid) -> Vec<usize> {
+        (0..4)
+            .filter(|&n| grid.cell(self.corners[n].0, self.corners[n].1) != self.pair())
+            .collect()
+    }
+
+    /// The rows and columns (as houses) which hold both given corners
+    fn shared_lines(&self, rules: &dyn Ruleset, a: usize, b: usize) -> Vec<usize> {
+        let (a, b) = (self.corners[a], self.corners[b]);
+        let mut ret = Vec::new();
+        if a.0 == b.0 {
+            ret.push(rules.rows().start + a.0);
+        }
+        if a.1 == b.1 {
+            ret.push(rules.cols().start + a.1);
+        }
+        ret
+    }
+
+    /// Every house (row, column, box or any extra house) which holds both
+    /// given corners
+    fn shared_houses(&self, rules: &dyn Ruleset, a: usize, b: usize) -> Vec<usize> {
+        let mut ret = self.shared_lines(rules, a, b);
+        ret.extend(
+            (0..rules.houses().len())
+                .filter(|house| !rules.rows().contains(house) && !rules.cols().contains(house))
+                .filter(|&house| house_contains(rules, house, self.corners[a]))
+                .filter(|&house| house_contains(rules, house, self.corners[b])),
+        );
+        ret
+    }
+}
+
+fn box_of(rules: &dyn Ruleset, pos: (usize, usize)) -> Option<usize> {
+    rules
+        .boxes()
+        .find(|&house| house_contains(rules, house, pos))
+}
+
+/// Whether the values in the corners of a rectangle could be swapped
+/// without breaking the rules.  Every other cell which sees a corner must
+/// also see one of the two corners sharing a line with it, which held the
+/// swapped in value before.  Under the normal rules this is the same as
+/// the rectangle spanning two boxes, but extra constraints (such as the
+/// diagonals) can rule out more.
+fn swappable(rules: &dyn Ruleset, corners: &[(usize, usize); 4]) -> bool {
+    (0..4).all(|n| {
+        let (row_mate, col_mate) = (corners[n ^ 1], corners[n ^ 2]);
+        rules
+            .sees(corners[n].0, corners[n].1)
+            .iter()
+            .filter(|pos| !corners.contains(pos))
+            .all(|&pos| sees(rules, pos, row_mate) || sees(rules, pos, col_mate))
+    })
+}
+
+/// Find every rectangle of unfixed cells spanning two boxes, for each
+/// pair of values all four corners share.
+fn rectangles(grid: &SGrid) -> Vec<Rectangle> {
+    let rules = grid.rules();
+    let mut ret = Vec::new();
+    for rows in combinations(&[0, 1, 2, 3, 4, 5, 6, 7, 8], 2) {
+        for cols in combinations(&[0, 1, 2, 3, 4, 5, 6, 7, 8], 2) {
+            let corners = [
+                (rows[0], cols[0]),
+                (rows[0], cols[1]),
+                (rows[1], cols[0]),
+                (rows[1], cols[1]),
+            ];
+            if corners
+                .iter()
+                .any(|&(row, col)| !matches!(grid.cell(row, col), SCell::Possible(_)))
+            {
+                continue;
+            }
+            let boxes = corners
+                .iter()
+                .map(|&pos| box_of(&*rules, pos))
+                .collect::<HashSet<_>>();
+            if boxes.len() != 2 {
+                continue;
+            }
+            let common = corners.iter().fold(SCell::default(), |acc, &(row, col)| {
+                acc.intersect(&grid.cell(row, col))
+            });
+            if common.possibilities() < 2 || !swappable(&*rules, &corners) {
+                continue;
+            }
+            for pair in combinations(&common.values().collect::<Vec<_>>(), 2) {
+                ret.push(Rectangle {
+                    corners,
+                    values: [pair[0], pair[1]],
+                });
+            }
+        }
+    }
+    ret
+}
+
+/// The unique rectangle techniques (types 1 to 6)
+///
+/// Each type relies on a rectangle (see [`Rectangle`]) needing at least
+/// one of its corners to be something other than its two values.
+///
+/// * Type 1: three corners have only the two values, so the fourth must
+///   be something else, and the two values are removed from it.
+/// * Type 2: the other two corners share a line and have one extra value
+///   in common and nothing more, so one of them is that extra value and
+///   it is removed from any cell which sees both.
+/// * Type 3: the other two corners share a house and, treated as a single
+///   cell of their extra values, form a naked subset with other cells in
+///   that house.  The subset's values are removed from the rest of the
+///   house.
+/// * Type 4: the other two corners share a house in which one of the two
+///   values can only be in those corners.  That value must be in one of
+///   them, and the other value can be removed from both.
+/// * Type 5: as type 2, but the corners with the extra value lie on a
+///   diagonal, or there are three of them.
+/// * Type 6: two diagonal corners have only the two values and one of the
+///   values forms an X-Wing on the rectangle.  That value cannot then be
+///   in the other two corners.
+///
+/// These are only valid if the grid has a single solution, and so are
+/// only used if the solver is told to assume that it does.
+pub struct UniqueRectangle {
+    kind: u8,
+}
+
+impl UniqueRectangle {
+    pub fn new(kind: u8) -> Self {
+        assert!(
+            (1..=6).contains(&kind),
+            "Unique rectangles are of type 1 to 6"
+        );
+        Self { kind }
+    }
+
+    fn type1(grid: &mut SGrid, step: &mut Step, rect: &Rectangle) {
+        let roof = rect.roof(grid);
+        if roof.len() != 1 {
+            return;
+        }
+        let (row, col) = rect.corners[roof[0]];
+        step.eliminate_all(grid, row, col, rect.pair());
+    }
+
+    fn type2or5(grid: &mut SGrid, step: &mut Step, rect: &Rectangle, diagonal: bool) {
+        let roof = rect.roof(grid);
+        if roof.len() < 2 || roof.len() > 3 {
+            return;
+        }
+        if diagonal
+            != (roof.len() == 3
+                || rect
+                    .shared_lines(&*grid.rules(), roof[0], roof[1])
+                    .is_empty())
+        {
+            return;
+        }
+        let cells = roof.iter().map(|&n| rect.corners[n]).collect::<Vec<_>>();
+        let extra = grid.cell(cells[0].0, cells[0].1);
+        if extra.possibilities() != 3
+            || cells.iter().any(|&(row, col)| grid.cell(row, col) != extra)
+        {
+            return;
+        }
+        let mut extra = extra;
+        extra.remove_all(rect.pair());
+        let value = extra.values().next().unwrap();
+        let rules = grid.rules();
+        for (row, col) in common_peers(&*rules, &cells) {
+            step.eliminate(grid, row, col, value);
+        }
+    }
+
+    fn type3(grid: &mut SGrid, step: &mut Step, rect: &Rectangle) {
+        let roof = rect.roof(grid);
+        if roof.len() != 2 {
+            return;
+        }
+        let roof = [rect.corners[roof[0]], rect.corners[roof[1]]];
+        let mut extra = match (
+            grid.cell(roof[0].0, roof[0].1),
+            grid.cell(roof[1].0, roof[1].1),
+        ) {
+            (SCell::Possible(a), SCell::Possible(b)) => SCell::Possible(a | b),
+            _ => unreachable!(),
+        };
+        extra.remove_all(rect.pair());
+        let [a, b] = [
+            rect.corners.iter().position(|&pos| pos == roof[0]).unwrap(),
+            rect.corners.iter().position(|&pos| pos == roof[1]).unwrap(),
+        ];
+        for house in rect.shared_houses(&*grid.rules(), a, b) {
+            let others = (0..9)
+                .filter(|&n| {
+                    let pos = grid.house_cell_to_row_col(house, n);
+                    !roof.contains(&pos) && grid.house_cell(house, n).possibilities() >= 2
+                })
+                .collect::<Vec<_>>();
+            for size in 1..=3 {
+                for subset in combinations(&others, size) {
+                    let values = subset.iter().fold(extra, |acc, &n| {
+                        match (acc, grid.house_cell(house, n)) {
+                            (SCell::Possible(a), SCell::Possible(b)) => SCell::Possible(a | b),
+                            _ => unreachable!(),
+                        }
+                    });
+                    if values.possibilities() != size + 1 {
+                        continue;
+                    }
+                    for n in 0..9 {
+                        let pos = grid.house_cell_to_row_col(house, n);
+                        if roof.contains(&pos) || subset.contains(&n) {
+                            continue;
+                        }
+                        step.eliminate_all(grid, pos.0, pos.1, values);
+                    }
+                    if step.acted() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn type4(grid: &mut SGrid, step: &mut Step, rect: &Rectangle) {
+        let roof = rect.roof(grid);
+        if roof.len() != 2 {
+            return;
+        }
+        let cells = [rect.corners[roof[0]], rect.corners[roof[1]]];
+        for house in rect.shared_houses(&*grid.rules(), roof[0], roof[1]) {
+            for (n, &value) in rect.values.iter().enumerate() {
+                let mask = house_positions(grid, house, value);
+                if mask.count_ones() != 2 {
+                    continue;
+                }
+                let other = rect.values[1 - n];
+                for &(row, col) in &cells {
+                    step.eliminate(grid, row, col, other);
+                }
+                if step.acted() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn type6(grid: &mut SGrid, step: &mut Step, rect: &Rectangle) {
+        let roof = rect.roof(grid);
+        if roof.len() != 2
+            || !rect
+                .shared_lines(&*grid.rules(), roof[0], roof[1])
+                .is_empty()
+        {
+            return;
+        }
+        let rules = grid.rules();
+        let lines = [
+            rules.rows().start + rect.corners[0].0,
+            rules.rows().start + rect.corners[3].0,
+            rules.cols().start + rect.corners[0].1,
+            rules.cols().start + rect.corners[3].1,
+        ];
+        for &value in &rect.values {
+            if lines
+                .iter()
+                .all(|&house| house_positions(grid, house, value).count_ones() == 2)
+            {
+                for &n in &roof {
+                    let (row, col) = rect.corners[n];
+                    step.eliminate(grid, row, col, value);
+                }
+                if step.acted() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Technique for UniqueRectangle {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            1 => "unique rectangle type 1",
+            2 => "unique rectangle type 2",
+            3 => "unique rectangle type 3",
+            4 => "unique rectangle type 4",
+            5 => "unique rectangle type 5",
+            6 => "unique rectangle type 6",
+            _ => unreachable!(),
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        match self.kind {
+            1 | 4 => 4.5,
+            2 | 5 | 6 => 4.6,
+            3 => 4.7,
+            _ => unreachable!(),
+        }
+    }
+
+    fn assumes_unique(&self) -> bool {
+        true
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        for rect in rectangles(grid) {
+            if rect.floor(grid).is_empty() {
+                continue;
+            }
+            let mut step = Step::new(self.name());
+            match self.kind {
+                1 => Self::type1(grid, &mut step, &rect),
+                2 => Self::type2or5(grid, &mut step, &rect, false),
+                3 => Self::type3(grid, &mut step, &rect),
+                4 => Self::type4(grid, &mut step, &rect),
+                5 => Self::type2or5(grid, &mut step, &rect, true),
+                6 => Self::type6(grid, &mut step, &rect),
+                _ => unreachable!(),
+            }
+            if step.acted() {
+                debug!(
+                    "Found a {} on {:?} at {:?}",
+                    self.name(),
+                    rect.values,
+                    rect.corners
+                );
+                return Acted(step.with_cells(rect.corners).with_digits(rect.values));
+            }
+        }
+        Stuck
+    }
+}
+
+/// The hidden unique rectangle technique
+///
+/// Given a rectangle (see [`Rectangle`]) where one corner has only the two
+/// values, look at the opposite corner.  If one of the values can only be
+/// in the rectangle in both that corner's row and its column, then that
+/// corner cannot be the other value, since that would force the deadly
+/// pattern.  Like the other uniqueness techniques, this is only valid if
+/// the grid has a single solution.
+pub struct HiddenUniqueRectangle;
+
+impl Technique for HiddenUniqueRectangle {
+    fn name(&self) -> &'static str {
+        "hidden unique rectangle"
+    }
+
+    fn difficulty(&self) -> f32 {
+        4.7
+    }
+
+    fn assumes_unique(&self) -> bool {
+        true
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        for rect in rectangles(grid) {
+            for corner in rect.floor(grid) {
+                let (row, col) = rect.corners[3 - corner];
+                for (n, &value) in rect.values.iter().enumerate() {
+                    if house_positions(grid, rules.rows().start + row, value).count_ones() != 2
+                        || house_positions(grid, rules.cols().start + col, value).count_ones() != 2
+                    {
+                        continue;
+                    }
+                    let other = rect.values[1 - n];
+                    let mut step = Step::new(self.name());
+                    if step.eliminate(grid, row, col, other) {
+                        debug!(
+                            "Found a hidden unique rectangle on {:?} at {:?}",
+                            rect.values, rect.corners
+                        );
+                        debug!("Removed {} from row {} col {}", other, row, col);
+                        return Acted(step.with_cells(rect.corners).with_digits(rect.values));
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// The BUG+1 technique
+///
+/// A bivalue universal grave (BUG) is a grid where every unfixed cell has
+/// exactly two possibilities, and each value appears exactly twice in
+/// each house.  Such a grid has either no solutions or several.  If every
+/// unfixed cell but one has two possibilities, and that one has three,
+/// then it must be the value which appears three times in its houses,
+/// since otherwise the grid would be a BUG.  This is only valid if the
+/// grid has a single solution.
+pub struct BugPlusOne;
+
+impl Technique for BugPlusOne {
+    fn name(&self) -> &'static str {
+        "bug+1"
+    }
+
+    fn difficulty(&self) -> f32 {
+        5.6
+    }
+
+    fn assumes_unique(&self) -> bool {
+        true
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        // The argument only holds when the cells each cell sees are just
+        // those it shares a row, column or box with.
+        let rules = grid.rules();
+        if rules.rows().len() + rules.cols().len() + rules.boxes().len() != rules.houses().len()
+            || !(0..9).all(|row| {
+                (0..9).all(|col| {
+                    rules.sees(row, col).iter().all(|&pos| {
+                        let mut houses = rules.rows().chain(rules.cols()).chain(rules.boxes());
+                        houses.any(|house| {
+                            house_contains(&*rules, house, pos)
+                                && house_contains(&*rules, house, (row, col))
+                        })
+                    })
+                })
+            })
+        {
+            return Stuck;
+        }
+        let mut extra = None;
+        for row in 0..9 {
+            for col in 0..9 {
+                match grid.cell(row, col).possibilities() {
+                    0 | 2 => {}
+                    3 if extra.is_none() => extra = Some((row, col)),
+                    _ => return Stuck,
+                }
+            }
+        }
+        let (row, col) = match extra {
+            Some(pos) => pos,
+            None => return Stuck,
+        };
+        let mut houses = vec![rules.rows().start + row, rules.cols().start + col];
+        houses.extend(box_of(&*rules, (row, col)));
+        for value in grid.cell(row, col).values() {
+            if houses
+                .iter()
+                .all(|&house| house_positions(grid, house, value).count_ones() == 3)
+            {
+                debug!("Found a bug+1 at row {} col {} on {}", row, col, value);
+                let mut step = Step::new(self.name())
+                    .with_cells([(row, col)])
+                    .with_digits([value]);
+                return match step.place(grid, row, col, value) {
+                    SResult::Continue | SResult::Finished => Acted(step),
+                    res => Failed(res),
+                };
+            }
+        }
+        Stuck
+    }
+}
+
+/// An almost locked set (ALS) is a set of N unfixed cells within a single
+/// house which, between them, have only N+1 possibilities.  If any one of
+/// those values is removed from the set, the remaining values are locked
+/// into the cells as a naked subset.  A single bivalue cell is the
+/// smallest ALS.
+#[derive(Debug)]
+struct Als {
+    cells: Vec<(usize, usize)>,
+    values: u16,
+    /// The cells of the set, as a bitmask of `row * 9 + col`
+    mask: u128,
+    /// For each value, the cells of the set which could be it, as a mask
+    value_cells: [u128; 10],
+}
+
+impl Als {
+    fn new(grid: &SGrid, cells: Vec<(usize, usize)>, values: u16) -> Self {
+        let mut mask = 0;
+        let mut value_cells = [0; 10];
+        for &(row, col) in &cells {
+            mask |= 1 << (row * 9 + col);
+            for value in grid.cell(row, col).values() {
+                value_cells[value as usize] |= 1 << (row * 9 + col);
+            }
+        }
+        Self {
+            cells,
+            values,
+            mask,
+            value_cells,
+        }
+    }
+
+    fn has(&self, value: u8) -> bool {
+        (self.values & (1 << value)) != 0
+    }
+
+    /// The cells of the set which could be the given value
+    fn cells_with(&self, grid: &SGrid, value: u8) -> Vec<(usize, usize)> {
+        self.cells
+            .iter()
+            .copied()
+            .filter(|&(row, col)| grid.cell(row, col).has(value))
+            .collect()
+    }
+
+    fn overlaps(&self, other: &Als) -> bool {
+        (self.mask & other.mask) != 0
+    }
+}
+
+/// The cells in a mask of `row * 9 + col` bits
+fn mask_cells(mut mask: u128) -> impl Iterator<Item = (usize, usize)> {
+    std::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let pos = mask.trailing_zeros() as usize;
+        mask &= mask - 1;
+        Some((pos / 9, pos % 9))
+    })
+}
+
+/// For each cell, the mask of the cells it sees
+fn peer_masks(rules: &dyn Ruleset) -> Vec<u128> {
+    (0..81)
+        .map(|pos| {
+            rules
+                .sees(pos / 9, pos % 9)
+                .iter()
+                .fold(0, |acc, &(row, col)| acc | 1 << (row * 9 + col))
+        })
+        .collect()
+}
+
+/// Find every almost locked set in the grid, across all houses.  Sets
+/// which appear in more than one house are only returned once.
+fn almost_locked_sets(grid: &SGrid) -> Vec<Als> {
+    let mut seen = HashSet::new();
+    let mut ret = Vec::new();
+    for house in 0..grid.rules().houses().len() {
+        let cells = (0..9)
+            .map(|n| grid.house_cell_to_row_col(house, n))
+            .filter(|&(row, col)| matches!(grid.cell(row, col), SCell::Possible(_)))
+            .collect::<Vec<_>>();
+        for size in 1..cells.len() {
+            for subset in combinations(&cells, size) {
+                let values =
+                    subset
+                        .iter()
+                        .fold(0u16, |acc, &(row, col)| match grid.cell(row, col) {
+                            SCell::Possible(v) => acc | v,
+                            _ => unreachable!(),
+                        });
+                if values.count_ones() as usize != size + 1 {
+                    continue;
+                }
+                let set = Als::new(grid, subset, values);
+                if seen.insert(set.mask) {
+                    ret.push(set);
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Every almost locked set in a grid, and the restricted common
+/// candidates between them.  These are values which two sets could both
+/// hold, where every cell in one set which could be the value sees every
+/// such cell in the other.  At most one of the sets can then hold the
+/// value.
+///
+/// Rather than compare every pair of sets, the sets are indexed by the
+/// cells which could hold each of their values.  Set B can only share a
+/// restricted common X with set A if all B's X cells are seen by all of
+/// A's, so only the sets indexed under X in those cells are looked at.
+struct AlsIndex {
+    sets: Vec<Als>,
+    /// For each set, the sets which do not overlap it and with which it
+    /// shares restricted commons, with those values in ascending order
+    links: Vec<BTreeMap<usize, Vec<u8>>>,
+}
+
+impl AlsIndex {
+    fn new(grid: &SGrid) -> Self {
+        let sets = almost_locked_sets(grid);
+        let peers = peer_masks(&*grid.rules());
+        // The sets which could hold each value in each cell
+        let mut holding = vec![Vec::new(); 81 * 10];
+        for (n, set) in sets.iter().enumerate() {
+            for value in 1..=9 {
+                for (row, col) in mask_cells(set.value_cells[value]) {
+                    holding[(row * 9 + col) * 10 + value].push(n);
+                }
+            }
+        }
+        let mut links = vec![BTreeMap::new(); sets.len()];
+        for (n, a) in sets.iter().enumerate() {
+            for value in (1..=9).filter(|&v| a.has(v)) {
+                let seen = mask_cells(a.value_cells[value as usize])
+                    .fold(!0, |acc, (row, col)| acc & peers[row * 9 + col]);
+                for (row, col) in mask_cells(seen) {
+                    for &m in &holding[(row * 9 + col) * 10 + value as usize] {
+                        let b = &sets[m];
+                        if (b.value_cells[value as usize] & !seen) != 0 || a.overlaps(b) {
+                            continue;
+                        }
+                        let rccs: &mut Vec<u8> = links[n].entry(m).or_default();
+                        if !rccs.contains(&value) {
+                            rccs.push(value);
+                        }
+                    }
+                }
+            }
+        }
+        Self { sets, links }
+    }
+}
+
+/// Remove the value from every cell which sees all the given cells
+fn eliminate_seeing_all(
+    grid: &mut SGrid,
+    step: &mut Step,
+    rules: &dyn Ruleset,
+    value: u8,
+    cells: &[(usize, usize)],
+) -> bool {
+    let mut acted = false;
+    for (row, col) in common_peers(rules, cells) {
+        if step.eliminate(grid, row, col, value) {
+            debug!("Removed {} from row {} col {}", value, row, col);
+            acted = true;
+        }
+    }
+    acted
+}
+
+/// The ALS-XZ technique
+///
+/// Two almost locked sets (see [`Als`]) which do not overlap and share a
+/// restricted common candidate X cannot both hold X, so at least one of
+/// them is locked without it.  If they also share a value Z then Z must
+/// be in one of the sets, and any cell which sees every cell of both sets
+/// which could be Z cannot be Z.
+///
+/// If the two sets share two restricted common candidates then they are
+/// doubly linked.  Both sets are then locked, with one of the two values
+/// in each.  Every value of each set is then certain to be in it, and can
+/// be removed from any cell outside the set which sees all the set's cells
+/// which could be that value.  The restricted common candidates are
+/// removed from cells seeing all of their cells in both sets.
+pub struct AlsXZ {
+    doubly: bool,
+}
+
+impl AlsXZ {
+    pub fn singly() -> Self {
+        Self { doubly: false }
+    }
+
+    pub fn doubly() -> Self {
+        Self { doubly: true }
+    }
+}
+
+impl Technique for AlsXZ {
+    fn name(&self) -> &'static str {
+        if self.doubly {
+            "doubly linked als-xz"
+        } else {
+            "als-xz"
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        if self.doubly {
+            7.2
+        } else {
+            7.0
+        }
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let index = AlsIndex::new(grid);
+        for (n, a) in index.sets.iter().enumerate() {
+            for (&m, rccs) in index.links[n].range(n + 1..) {
+                let b = &index.sets[m];
+                if (a.values & b.values).count_ones() < 2 {
+                    continue;
+                }
+                let mut step = Step::new(self.name());
+                match (self.doubly, rccs.len()) {
+                    (false, 1) => {
+                        for value in (1..=9).filter(|&v| v != rccs[0] && a.has(v) && b.has(v)) {
+                            let mut cells = a.cells_with(grid, value);
+                            cells.extend(b.cells_with(grid, value));
+                            eliminate_seeing_all(grid, &mut step, &*rules, value, &cells);
+                        }
+                    }
+                    (true, 2) => {
+                        for &value in rccs {
+                            let mut cells = a.cells_with(grid, value);
+                            cells.extend(b.cells_with(grid, value));
+                            eliminate_seeing_all(grid, &mut step, &*rules, value, &cells);
+                        }
+                        for set in &[a, b] {
+                            for value in (1..=9).filter(|&v| set.has(v) && !rccs.contains(&v)) {
+                                let cells = set.cells_with(grid, value);
+                                eliminate_seeing_all(grid, &mut step, &*rules, value, &cells);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if step.acted() {
+                    debug!(
+                        "Found an {} on {:?} and {:?} linked by {:?}",
+                        self.name(),
+                        a,
+                        b,
+                        rccs
+                    );
+                    let cells = a.cells.iter().chain(b.cells.iter()).copied();
+                    return Acted(step.with_cells(cells).with_digits(rccs.iter().copied()));
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// The ALS-XY-Wing technique
+///
+/// Three almost locked sets (see [`Als`]), A, B and C, none of which
+/// overlap.  A and C share a restricted common candidate X, and B and C
+/// share a different restricted common candidate Y.  C cannot hold both X
+/// and Y, so at least one of A and B is locked.  If A and B share a value
+/// Z (other than X and Y) then Z must be in one of them, and can be
+/// removed from any cell which sees all the cells of A and B which could
+/// be Z.
+pub struct AlsXYWing;
+
+impl Technique for AlsXYWing {
+    fn name(&self) -> &'static str {
+        "als-xy-wing"
+    }
+
+    fn difficulty(&self) -> f32 {
+        7.3
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let AlsIndex { sets, links } = AlsIndex::new(grid);
+        for (c, pivot) in sets.iter().enumerate() {
+            let wings = links[c].keys().copied().collect::<Vec<_>>();
+            for pair in combinations(&wings, 2) {
+                let (a, b) = (&sets[pair[0]], &sets[pair[1]]);
+                if a.overlaps(b) {
+                    continue;
+                }
+                for &x in &links[c][&pair[0]] {
+                    for &y in links[c][&pair[1]].iter().filter(|&&y| y != x) {
+                        let mut step = Step::new(self.name());
+                        for z in (1..=9).filter(|&z| z != x && z != y && a.has(z) && b.has(z)) {
+                            let mut cells = a.cells_with(grid, z);
+                            cells.extend(b.cells_with(grid, z));
+                            eliminate_seeing_all(grid, &mut step, &*rules, z, &cells);
+                        }
+                        if step.acted() {
+                            debug!(
+                                "Found an als-xy-wing on {:?} and {:?} through {:?} linked by {} and {}",
+                                a, b, pivot, x, y
+                            );
+                            let cells = a.cells.iter().chain(&b.cells).chain(&pivot.cells);
+                            return Acted(step.with_cells(cells.copied()).with_digits([x, y]));
+                        }
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// The death blossom technique
+///
+/// A death blossom is a stem cell, and for each of the stem's possible
+/// values an almost locked set (see [`Als`]), called a petal, in which
+/// every cell which could be that value sees the stem.  Whichever value
+/// the stem takes, the corresponding petal loses that value and is
+/// locked.  If every petal could also be some other value Z then Z must
+/// be in one of the petals, and can be removed from any cell which sees
+/// every cell of every petal which could be Z.
+pub struct DeathBlossom;
+
+impl Technique for DeathBlossom {
+    fn name(&self) -> &'static str {
+        "death blossom"
+    }
+
+    fn difficulty(&self) -> f32 {
+        7.6
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let sets = almost_locked_sets(grid);
+        let peers = peer_masks(&*rules);
+        for row in 0..9 {
+            for col in 0..9 {
+                let stem = grid.cell(row, col);
+                if !(2..=3).contains(&stem.possibilities()) {
+                    continue;
+                }
+                for z in (1..=9).filter(|&z| !stem.has(z)) {
+                    // For each of the stem's values, the petals which could
+                    // be attached by it and which could also be Z
+                    let petals = stem
+                        .values()
+                        .map(|value| {
+                            sets.iter()
+                                .filter(|set| {
+                                    set.has(value)
+                                        && set.has(z)
+                                        && !set.cells.contains(&(row, col))
+                                        && (set.value_cells[value as usize] & !peers[row * 9 + col])
+                                            == 0
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>();
+                    if petals.iter().any(Vec::is_empty) {
+                        continue;
+                    }
+                    let mut chosen = Vec::new();
+                    let mut step = Step::new(self.name());
+                    if Self::blossom(grid, &mut step, &*rules, z, &petals, &mut chosen) {
+                        debug!(
+                            "Found a death blossom on stem row {} col {} with petals {:?}",
+                            row, col, chosen
+                        );
+                        let cells = chosen.iter().flat_map(|petal| petal.cells.iter().copied());
+                        let step = step.with_cells([(row, col)]).with_cells(cells);
+                        return Acted(step.with_digits([z]));
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+impl DeathBlossom {
+    /// Choose one petal for each of the stem's values, none of which
+    /// overlap, and try eliminating Z using them.
+    fn blossom<'a>(
+        grid: &mut SGrid,
+        step: &mut Step,
+        rules: &dyn Ruleset,
+        z: u8,
+        petals: &[Vec<&'a Als>],
+        chosen: &mut Vec<&'a Als>,
+    ) -> bool {
+        if chosen.len() == petals.len() {
+            let cells = chosen
+                .iter()
+                .flat_map(|petal| petal.cells_with(grid, z))
+                .collect::<Vec<_>>();
+            return eliminate_seeing_all(grid, step, rules, z, &cells);
+        }
+        for &petal in &petals[chosen.len()] {
+            if chosen.iter().any(|other| other.overlaps(petal)) {
+                continue;
+            }
+            chosen.push(petal);
+            if Self::blossom(grid, step, rules, z, petals, chosen) {
+                return true;
+            }
+            chosen.pop();
+        }
+        false
+    }
+}
+
+/// Union of the possibilities of a set of unfixed cells, as a bitmask
+fn possibilities_of(grid: &SGrid, cells: &[(usize, usize)]) -> u16 {
+    cells
+        .iter()
+        .fold(0, |acc, &(row, col)| match grid.cell(row, col) {
+            SCell::Possible(v) => acc | v,
+            SCell::Fixed(_) => acc,
+        })
+}
+
+/// The Sue de Coq technique
+///
+/// Take two or three unfixed cells in the intersection of a box and a
+/// line which, between them, have at least two more possibilities than
+/// they have cells.  Pair them with some cells from the rest of the line
+/// and some from the rest of the box, such that the line cells and box
+/// cells share no possibilities and all the cells together have exactly
+/// as many possibilities as cells.  Each value can then appear only once
+/// across all the cells, so every value must be placed in them.  Values
+/// of the line cells can be removed from the rest of the line, values of
+/// the box cells from the rest of the box, and any other values from the
+/// rest of both.
+pub struct SueDeCoq;
+
+impl Technique for SueDeCoq {
+    fn name(&self) -> &'static str {
+        "sue de coq"
+    }
+
+    fn difficulty(&self) -> f32 {
+        7.0
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let rules = grid.rules();
+        let unfixed = |grid: &SGrid, house: usize| {
+            (0..9)
+                .map(|n| grid.house_cell_to_row_col(house, n))
+                .filter(|&(row, col)| matches!(grid.cell(row, col), SCell::Possible(_)))
+                .collect::<Vec<_>>()
+        };
+        for boxhouse in rules.boxes() {
+            let box_cells = unfixed(grid, boxhouse);
+            for &line in rules.overlapping_houses(boxhouse) {
+                let line_cells = unfixed(grid, line);
+                let (isect, line_rest): (Vec<_>, Vec<_>) =
+                    line_cells.iter().partition(|pos| box_cells.contains(pos));
+                let box_rest = box_cells
+                    .iter()
+                    .copied()
+                    .filter(|pos| !isect.contains(pos))
+                    .collect::<Vec<_>>();
+                for size in 2..=isect.len() {
+                    for cells in combinations(&isect, size) {
+                        let values = possibilities_of(grid, &cells);
+                        if (values.count_ones() as usize) < size + 2 {
+                            continue;
+                        }
+                        for line_size in 1..=line_rest.len() {
+                            for lset in combinations(&line_rest, line_size) {
+                                let lvalues = possibilities_of(grid, &lset);
+                                if (lvalues & values) == 0 {
+                                    continue;
+                                }
+                                for box_size in 1..=box_rest.len() {
+                                    for bset in combinations(&box_rest, box_size) {
+                                        let bvalues = possibilities_of(grid, &bset);
+                                        if (bvalues & values) == 0 || (bvalues & lvalues) != 0 {
+                                            continue;
+                                        }
+                                        let all = values | lvalues | bvalues;
+                                        if all.count_ones() as usize != size + line_size + box_size
+                                        {
+                                            continue;
+                                        }
+                                        let mut step = Step::new(self.name());
+                                        for &(row, col) in &line_rest {
+                                            if !lset.contains(&(row, col)) {
+                                                step.eliminate_all(
+                                                    grid,
+                                                    row,
+                                                    col,
+                                                    SCell::Possible(all & !bvalues),
+                                                );
+                                            }
+                                        }
+                                        for &(row, col) in &box_rest {
+                                            if !bset.contains(&(row, col)) {
+                                                step.eliminate_all(
+                                                    grid,
+                                                    row,
+                                                    col,
+                                                    SCell::Possible(all & !lvalues),
+                                                );
+                                            }
+                                        }
+                                        for &(row, col) in &isect {
+                                            if !cells.contains(&(row, col)) {
+                                                step.eliminate_all(
+                                                    grid,
+                                                    row,
+                                                    col,
+                                                    SCell::Possible(all),
+                                                );
+                                            }
+                                        }
+                                        if step.acted() {
+                                            debug!(
+                                                "Found a sue de coq on {:?} with line cells {:?} and box cells {:?}",
+                                                cells, lset, bset
+                                            );
+                                            let pattern =
+                                                cells.iter().chain(&lset).chain(&bset).copied();
+                                            let digits = SCell::Possible(all).values();
+                                            return Acted(
+                                                step.with_cells(pattern).with_digits(digits),
+                                            );
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Stuck
+    }
+}
+
+/// The default limit on how many steps a forcing chain or net may take
+/// when following the consequences of an assumption.
+pub const DEFAULT_FORCING_DEPTH: usize = 64;
+
+/// What a forcing chain or net branches on
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Forcing {
+    /// Each possibility of a single cell
+    Cell,
+    /// Each place a value could go in a house
+    Unit,
+    /// A single candidate being true, or being false
+    Digit,
+}
+
+/// How a forcing chain follows the consequences of an assumption
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Spread {
+    /// Singles only
+    Chain,
+    /// Singles, subsets and locked candidates
+    Net,
+    /// As a net, and also cell forcing chains within each branch
+    Nested,
+}
+
+/// An assumption made at the start of a branch
+#[derive(Copy, Clone, Debug)]
+enum Assumption {
+    Place(usize, usize, u8),
+    Remove(usize, usize, u8),
+}
+
+/// Whether the grid can no longer be solved, either because a cell has
+/// no possibilities left or because a house has nowhere for a value.
+fn broken(grid: &SGrid) -> bool {
+    (0..9).any(|row| (0..9).any(|col| grid.cell(row, col).values().len() == 0))
+        || (0..grid.rules().houses().len()).any(|house| {
+            let content = grid.house(house);
+            (1..=9).any(|value| !content.iter().any(|cell| cell.has(value)))
+        })
+}
+
+/// The forcing chain and forcing net techniques
+///
+/// These are the last resort.  We take a set of assumptions, exactly one
+/// of which must be true, and for each we copy the grid, make the
+/// assumption and follow its consequences.  Anything which is true in
+/// every branch which does not end in a contradiction must be true of
+/// the grid.  The branches are:
+///
+/// * Cell: each possibility of an unfixed cell
+/// * Unit: each cell in a house which could be a given value
+/// * Digit: a candidate being the value, and it not being the value
+///
+/// A forcing chain follows each branch using only singles, so each step
+/// forces exactly one more cell.  A forcing net also uses subsets and
+/// locked candidates, so each step may depend on several earlier ones.
+/// A nested forcing net goes further still, and tries a cell forcing
+/// chain inside each branch whenever the net itself gets stuck; this is
+/// slow, but it cracks the hardest grids we have.  Each branch takes at
+/// most `depth` steps.
+pub struct ForcingChain {
+    kind: Forcing,
+    spread: Spread,
+    depth: usize,
+    /// Assumption sets with more branches than this are not tried
+    max_branches: usize,
+    propagators: Vec<Box<dyn Technique>>,
+}
+
+impl ForcingChain {
+    pub fn chain(kind: Forcing, depth: usize) -> Self {
+        Self {
+            kind,
+            spread: Spread::Chain,
+            depth,
+            max_branches: usize::MAX,
+            propagators: vec![
+                Box::new(NakedSingle),
+                Box::new(HiddenSingle::in_any_house()),
+            ],
+        }
+    }
+
+    pub fn net(kind: Forcing, depth: usize) -> Self {
+        Self {
+            kind,
+            spread: Spread::Net,
+            depth,
+            max_branches: usize::MAX,
+            propagators: vec![
+                Box::new(NakedSingle),
+                Box::new(HiddenSingle::in_any_house()),
+                Box::new(NakedSubset::new(2)),
+                Box::new(HiddenSubset::new(2)),
+                Box::new(Pointing),
+                Box::new(BoxLineReduction),
+                Box::new(NakedSubset::new(3)),
+                Box::new(HiddenSubset::new(3)),
+            ],
+        }
+    }
+
+    pub fn nested(kind: Forcing, depth: usize) -> Self {
+        let mut ret = Self::net(kind, depth);
+        ret.spread = Spread::Nested;
+        // Only branch on cells with few possibilities inside each branch,
+        // or grids with many solutions take minutes to give up on
+        let mut inner = ForcingChain::chain(Forcing::Cell, depth);
+        inner.max_branches = 3;
+        ret.propagators.push(Box::new(inner));
+        ret
+    }
+
+    /// Follow the consequences of an assumption on a copy of the grid.
+    /// Returns None if the assumption leads to a contradiction.
+    fn branch(&mut self, grid: &SGrid, assumption: Assumption) -> Option<SGrid> {
+        let mut grid = grid.clone();
+        match assumption {
+            Assumption::Place(row, col, value) => match grid.set_cell(row, col, value) {
+                SResult::Continue | SResult::Finished => {}
+                _ => return None,
+            },
+            Assumption::Remove(row, col, value) => {
+                grid.remove(row, col, value);
+            }
+        }
+        for _ in 0..self.depth {
+            if broken(&grid) {
+                return None;
+            }
+            if let SResult::Finished = grid.done() {
+                break;
+            }
+            let mut acted = false;
+            for technique in self.propagators.iter_mut() {
+                match technique.step(&mut grid) {
+                    Acted(_) => {
+                        acted = true;
+                        break;
+                    }
+                    Failed(_) => return None,
+                    _ => {}
+                }
+            }
+            if !acted {
+                break;
+            }
+        }
+        if broken(&grid) {
+            None
+        } else {
+            Some(grid)
+        }
+    }
+
+    /// The sets of assumptions, exactly one of each of which must hold
+    fn assumption_sets(&self, grid: &SGrid) -> Vec<Vec<Assumption>> {
+        let mut ret = Vec::new();
+        match self.kind {
+            Forcing::Cell => {
+                for row in 0..9 {
+                    for col in 0..9 {
+                        if let cell @ SCell::Possible(_) = grid.cell(row, col) {
+                            ret.push(
+                                cell.values()
+                                    .map(|value| Assumption::Place(row, col, value))
+                                    .collect(),
+                            );
+                        }
+                    }
+                }
+            }
+            Forcing::Unit => {
+                for house in 0..grid.rules().houses().len() {
+                    for value in 1..=9 {
+                        let mask = house_positions(grid, house, value);
+                        if mask.count_ones() < 2 {
+                            continue;
+                        }
+                        ret.push(
+                            (0..9)
+                                .filter(|n| (mask & (1 << n)) != 0)
+                                .map(|n| {
+                                    let (row, col) = grid.house_cell_to_row_col(house, n);
+                                    Assumption::Place(row, col, value)
+                                })
+                                .collect(),
+                        );
+                    }
+                }
+            }
+            Forcing::Digit => {
+                for row in 0..9 {
+                    for col in 0..9 {
+                        if let cell @ SCell::Possible(_) = grid.cell(row, col) {
+                            for value in cell.values() {
+                                ret.push(vec![
+                                    Assumption::Place(row, col, value),
+                                    Assumption::Remove(row, col, value),
+                                ]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // Try the sets with the fewest branches first
+        ret.retain(|set| set.len() <= self.max_branches);
+        ret.sort_by_key(Vec::len);
+        ret
+    }
+}
+
+impl Technique for ForcingChain {
+    fn name(&self) -> &'static str {
+        match (self.kind, self.spread) {
+            (Forcing::Cell, Spread::Chain) => "cell forcing chain",
+            (Forcing::Unit, Spread::Chain) => "unit forcing chain",
+            (Forcing::Digit, Spread::Chain) => "digit forcing chain",
+            (Forcing::Cell, Spread::Net) => "cell forcing net",
+            (Forcing::Unit, Spread::Net) => "unit forcing net",
+            (Forcing::Digit, Spread::Net) => "digit forcing net",
+            (Forcing::Cell, Spread::Nested) => "nested cell forcing net",
+            (Forcing::Unit, Spread::Nested) => "nested unit forcing net",
+            (Forcing::Digit, Spread::Nested) => "nested digit forcing net",
+        }
+    }
+
+    fn difficulty(&self) -> f32 {
+        match (self.kind, self.spread) {
+            (Forcing::Cell, Spread::Chain) => 8.3,
+            (Forcing::Unit, Spread::Chain) => 8.5,
+            (Forcing::Digit, Spread::Chain) => 8.7,
+            (Forcing::Cell, Spread::Net) => 9.0,
+            (Forcing::Unit, Spread::Net) => 9.2,
+            (Forcing::Digit, Spread::Net) => 9.5,
+            (Forcing::Cell, Spread::Nested) => 10.0,
+            (Forcing::Unit, Spread::Nested) => 10.2,
+            (Forcing::Digit, Spread::Nested) => 10.5,
+        }
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        if let res @ SResult::Insoluable(..) = grid.done() {
+            return Failed(res);
+        }
+        for assumptions in self.assumption_sets(grid) {
+            let branches = assumptions
+                .iter()
+                .filter_map(|&assumption| self.branch(grid, assumption))
+                .collect::<Vec<_>>();
+            if branches.is_empty() {
+                debug!("Every branch of {:?} is a contradiction", assumptions);
+                let (row, col) = match assumptions[0] {
+                    Assumption::Place(row, col, _) | Assumption::Remove(row, col, _) => (row, col),
+                };
+                return Failed(SResult::Insoluable(row, col));
+            }
+            let mut places = Vec::new();
+            let mut removes = Vec::new();
+            for row in 0..9 {
+                for col in 0..9 {
+                    let cell = grid.cell(row, col);
+                    if let SCell::Fixed(_) = cell {
+                        continue;
+                    }
+                    match branches[0].cell(row, col) {
+                        SCell::Fixed(value)
+                            if branches
+                                .iter()
+                                .all(|b| b.cell(row, col) == SCell::Fixed(value)) =>
+                        {
+                            places.push((row, col, value));
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    for value in cell.values() {
+                        if !branches.iter().any(|b| b.cell(row, col).has(value)) {
+                            removes.push((row, col, value));
+                        }
+                    }
+                }
+            }
+            if places.is_empty() && removes.is_empty() {
+                continue;
+            }
+            debug!(
+                "Found a {} from {:?}, placing {:?} and removing {:?}",
+                self.name(),
+                assumptions,
+                places,
+                removes
+            );
+            let mut step = Step::new(self.name());
+            for assumption in &assumptions {
+                step = match *assumption {
+                    Assumption::Place(row, col, value) | Assumption::Remove(row, col, value) => {
+                        step.with_cells([(row, col)]).with_digits([value])
+                    }
+                };
+            }
+            for (row, col, value) in removes {
+                step.eliminate(grid, row, col, value);
+            }
+            for (row, col, value) in places {
+                match step.place(grid, row, col, value) {
+                    SResult::Continue | SResult::Finished => {}
+                    res => return Failed(res),
+                }
+            }
+            return Acted(step);
+        }
+        Stuck
+    }
+}
+
+/// The backtracking technique
+///
+/// This is not a logical technique at all; it simply finds a solution by
+/// trial and error (see [`backtrack`]) and fills it in.  It is only used
+/// by the complete solver set, so that every valid grid gets an answer.
+pub struct Backtrack;
+
+impl Technique for Backtrack {
+    fn name(&self) -> &'static str {
+        "backtracking"
+    }
+
+    fn difficulty(&self) -> f32 {
+        11.0
+    }
+
+    fn step(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let solution = match backtrack(grid) {
+            Some(solution) => solution,
+            None => return Stuck,
+        };
+        let mut step = Step::new(self.name());
+        for row in 0..9 {
+            for col in 0..9 {
+                if let SCell::Fixed(value) = solution.cell(row, col) {
+                    match step.place(grid, row, col, value) {
+                        SResult::Continue | SResult::Finished => {}
+                        res => return Failed(res),
+                    }
+                }
+            }
+        }
+        Acted(step)
+    }
+}
+
+pub struct SolverSet {
+    techniques: Vec<Box<dyn Technique>>,
+    log: Vec<Step>,
+    assume_unique: bool,
+}
+
+impl SolverSet {
+    pub fn new() -> SolverSet {
+        Self {
+            techniques: Vec::new(),
+            log: Vec::new(),
+            assume_unique: false,
+        }
+    }
+
+    pub fn add_technique<T>(&mut self, t: T)
+    where
+        T: Technique + 'static,
+    {
+        self.techniques.push(Box::new(t));
+    }
+
+    /// Reorder the techniques from easiest to hardest, so that each step
+    /// is the easiest one available.  Techniques of equal difficulty keep
+    /// their order.
+    pub fn by_difficulty(&mut self) {
+        self.techniques
+            .sort_by(|a, b| a.difficulty().total_cmp(&b.difficulty()));
+    }
+
+    /// Allow techniques which are only valid if the grid has a single
+    /// solution.  Published puzzles are expected to, but if the grid has
+    /// several then these techniques may remove valid solutions.
+    pub fn assume_unique(&mut self, assume: bool) {
+        self.assume_unique = assume;
+    }
+
+    pub fn solve_grid(&mut self, grid: &mut SGrid) -> SolveStepResult {
+        let mut tnum = 0;
+        'outer: loop {
+            if let SResult::Finished = grid.done() {
+                break Finished;
+            }
+            if tnum == self.techniques.len() {
+                break Stuck;
+            }
+            if self.techniques[tnum].assumes_unique() && !self.assume_unique {
+                tnum += 1;
+                continue;
+            }
+            debug!("Trying {}", self.techniques[tnum].name());
+            match self.techniques[tnum].step(grid) {
+                Stuck => {
+                    debug!("{} is stuck", self.techniques[tnum].name());
+                    tnum += 1;
+                }
+                Acted(mut step) => {
+                    step.difficulty = self.techniques[tnum].difficulty();
+                    debug!("{}", step);
+                    self.log.push(step);
+                    tnum = 0;
+                }
+                res => {
+                    break res;
+                }
+            }
+            for row in 0..9 {
+                for col in 0..9 {
+                    if grid.cell(row, col).values().len() == 0 {
+                        debug!("Well, that broke the grid!");
+                        break 'outer Stuck;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The step the easiest technique which can act on the grid would
+    /// take, without changing the grid.  Techniques of equal difficulty
+    /// are tried in the order they were added.  There is no step for a
+    /// solved or broken grid; use [`SGrid::done`] to tell those apart.
+    pub fn hint(&mut self, grid: &SGrid) -> Option<Step> {
+        let mut order = (0..self.techniques.len())
+            .filter(|&n| self.assume_unique || !self.techniques[n].assumes_unique())
+            .collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&self.techniques[a], &self.techniques[b]);
+            a.difficulty().total_cmp(&b.difficulty())
+        });
+        for n in order {
+            let technique = &mut self.techniques[n];
+            match technique.step(&mut grid.clone()) {
+                Acted(mut step) => {
+                    step.difficulty = technique.difficulty();
+                    return Some(step);
+                }
+                Stuck => {}
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// The steps taken so far, in order
+    pub fn log(&self) -> &[Step] {
+        &self.log
+    }
+
+    pub fn dump_log(&self) {
+        for (n, step) in self.log.iter().enumerate() {
+            println!("{:>3}. [{:.1}] {}", n + 1, step.difficulty, step);
+        }
+    }
+
+    /// The full set of techniques, followed by backtracking so that any
+    /// grid with a solution is solved.
+    pub fn complete() -> SolverSet {
+        let mut ret = SolverSet::full();
+        ret.add_technique(Backtrack);
+        ret
+    }
+
+    pub fn full() -> SolverSet {
+        SolverSet::full_with_depth(DEFAULT_FORCING_DEPTH)
+    }
+
+    /// The full set of techniques, with the given limit on the depth of
+    /// the forcing chains and nets which are tried last.
+    pub fn full_with_depth(depth: usize) -> SolverSet {
+        let mut ret = SolverSet::new();
         ret.add_technique(NakedSingle);
-        ret.add_technique(HiddenSingle);
-        ret.add_technique(NakedPair);
-        ret.add_technique(HiddenPair);
+        ret.add_technique(HiddenSingle::in_boxes());
+        ret.add_technique(HiddenSingle::in_any_house());
+        ret.add_technique(NakedSubset::new(2));
+        ret.add_technique(HiddenSubset::new(2));
         ret.add_technique(Pointing);
+        ret.add_technique(BoxLineReduction);
+        ret.add_technique(NakedSubset::new(3));
+        ret.add_technique(HiddenSubset::new(3));
+        ret.add_technique(NakedSubset::new(4));
+        ret.add_technique(HiddenSubset::new(4));
+        ret.add_technique(BasicFish::new(2));
+        ret.add_technique(BasicFish::new(3));
+        ret.add_technique(BasicFish::new(4));
+        ret.add_technique(Skyscraper);
+        ret.add_technique(TwoStringKite);
+        ret.add_technique(TurbotFish);
+        ret.add_technique(EmptyRectangle);
+        ret.add_technique(FinnedFish::finned(2));
+        ret.add_technique(FinnedFish::sashimi(2));
+        ret.add_technique(FinnedFish::finned(3));
+        ret.add_technique(FinnedFish::sashimi(3));
+        ret.add_technique(FinnedFish::finned(4));
+        ret.add_technique(FinnedFish::sashimi(4));
+        ret.add_technique(XYWing);
+        ret.add_technique(XYZWing);
+        ret.add_technique(WWing);
+        ret.add_technique(UniqueRectangle::new(1));
+        ret.add_technique(UniqueRectangle::new(2));
+        ret.add_technique(UniqueRectangle::new(4));
+        ret.add_technique(UniqueRectangle::new(5));
+        ret.add_technique(UniqueRectangle::new(6));
+        ret.add_technique(UniqueRectangle::new(3));
+        ret.add_technique(HiddenUniqueRectangle);
+        ret.add_technique(BugPlusOne);
+        ret.add_technique(SimpleColouring);
+        ret.add_technique(MultiColouring);
+        ret.add_technique(XChain);
+        ret.add_technique(XYChain);
+        ret.add_technique(SueDeCoq);
+        ret.add_technique(AlsXZ::singly());
+        ret.add_technique(AlsXZ::doubly());
+        ret.add_technique(AlsXYWing);
+        ret.add_technique(AlternatingInferenceChain);
+        ret.add_technique(NiceLoop);
+        ret.add_technique(DeathBlossom);
+        ret.add_technique(ForcingChain::chain(Forcing::Cell, depth));
+        ret.add_technique(ForcingChain::chain(Forcing::Unit, depth));
+        ret.add_technique(ForcingChain::chain(Forcing::Digit, depth));
+        ret.add_technique(ForcingChain::net(Forcing::Cell, depth));
+        ret.add_technique(ForcingChain::net(Forcing::Unit, depth));
+        ret.add_technique(ForcingChain::net(Forcing::Digit, depth));
+        ret.add_technique(ForcingChain::nested(Forcing::Cell, depth));
+        ret.add_technique(ForcingChain::nested(Forcing::Unit, depth));
+        ret.add_technique(ForcingChain::nested(Forcing::Digit, depth));
         ret
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Normal;
+    use crate::test_grid;
+
+    const EASY: &str =
+        "...26.7.168..7..9.19...45..82.1...4...46.29...5...3.28..93...74.4..5..367.3.18...";
+
+    #[test]
+    fn forcing_chain_fails_only_on_insoluble_grids() {
+        let mut grid = test_grid(Normal::new(), EASY);
+        let solution = backtrack(&grid).unwrap();
+        // Put a wrong value in the first cell where that does not conflict
+        // at once, so there is no longer any solution
+        let wrong = (0..81).find_map(|pos| {
+            let (row, col) = (pos / 9, pos % 9);
+            let cell = grid.cell(row, col);
+            cell.values()
+                .filter(|&value| solution.cell(row, col) != SCell::Fixed(value))
+                .find(|&value| {
+                    let mut next = grid.clone();
+                    next.set_cell(row, col, value) == SResult::Continue
+                })
+                .map(|value| (row, col, value))
+        });
+        let (row, col, value) = wrong.unwrap();
+        assert_eq!(grid.set_cell(row, col, value), SResult::Continue);
+        assert!(backtrack(&grid).is_none());
+
+        let mut forcing = ForcingChain::chain(Forcing::Cell, 20);
+        loop {
+            match forcing.step(&mut grid) {
+                Acted(_) => assert!(backtrack(&grid).is_none()),
+                Failed(SResult::Insoluable(..)) => break,
+                _ => panic!("forcing chain missed the contradiction"),
+            }
+        }
+
+        // On the real puzzle no set of branches is all contradictions
+        let mut grid = test_grid(Normal::new(), EASY);
+        loop {
+            match forcing.step(&mut grid) {
+                Acted(_) => {}
+                Failed(_) => panic!("forcing chain failed on a soluble grid"),
+                _ => break,
+            }
+        }
+        assert_eq!(grid.to_string(), solution.to_string());
+    }
+
+    #[test]
+    fn hint_is_easiest_step_and_leaves_grid_alone() {
+        let grid = test_grid(Normal::new(), EASY);
+        let before = grid.to_string();
+        let mut solver = SolverSet::full();
+        // Put the hardest technique first, so insertion order would pick it
+        solver.techniques.rotate_right(1);
+        let step = solver.hint(&grid).expect("no hint for an easy grid");
+        // Hidden singles in a box, at 1.2, are the easiest technique
+        assert_eq!(step.technique, "hidden single in box");
+        assert!(!step.placements.is_empty());
+        assert_eq!(grid.to_string(), before);
+        for (row, col, _) in step.placements {
+            assert!(matches!(grid.cell(row, col), SCell::Possible(_)));
+        }
+    }
+
+    #[test]
+    fn no_hint_for_broken_or_solved_grids() {
+        let mut solver = SolverSet::full();
+        let mut grid = SGrid::new(Normal::new());
+        for value in 1..=9 {
+            grid.remove(0, 0, value);
+        }
+        assert_eq!(grid.done(), SResult::Insoluable(0, 0));
+        assert!(solver.hint(&grid).is_none());
+
+        let solution = backtrack(&grid.blank()).unwrap();
+        assert_eq!(solution.done(), SResult::Finished);
+        assert!(solver.hint(&solution).is_none());
+    }
+
+    #[test]
+    fn full_solves_hard_grids() {
+        for line in include_str!("../newgrids2.txt").lines() {
+            let mut grid = test_grid(Normal::new(), line);
+            if crate::dlx::count_solutions(&grid, 2) != 1 {
+                continue;
+            }
+            let mut solver = SolverSet::full();
+            assert!(
+                matches!(solver.solve_grid(&mut grid), Finished),
+                "stuck on {}",
+                line
+            );
+        }
+    }
+
+    /// Build a grid from its pencil marks: 81 groups of digits separated
+    /// by whitespace, row by row, where a single digit is a fixed cell.
+    fn candidate_grid(marks: &str) -> SGrid {
+        let marks = marks.split_whitespace().collect::<Vec<_>>();
+        assert_eq!(marks.len(), 81);
+        let mut grid = SGrid::new(Normal::new());
+        for (pos, mark) in marks.iter().enumerate() {
+            if mark.len() == 1 {
+                let value = mark.parse().unwrap();
+                assert_eq!(grid.set_cell(pos / 9, pos % 9, value), SResult::Continue);
+            }
+        }
+        for (pos, mark) in marks.iter().enumerate() {
+            if mark.len() > 1 {
+                let (row, col) = (pos / 9, pos % 9);
+                for value in 1..=9 {
+                    if !mark.contains((b'0' + value) as char) {
+                        grid.remove(row, col, value);
+                    }
+                }
+                let values = grid.cell(row, col).values().collect::<Vec<_>>();
+                assert_eq!(
+                    values.len(),
+                    mark.len(),
+                    "r{}c{} is {:?}",
+                    row + 1,
+                    col + 1,
+                    values
+                );
+            }
+        }
+        grid
+    }
+
+    /// Take a step with a technique on a grid given as pencil marks, and
+    /// check it makes exactly the expected changes, written as they are in
+    /// a step, such as "r5c1=4" or "r5c4<>3".
+    fn check_step<T: Technique>(mut technique: T, marks: &str, expected: &[&str]) {
+        let mut grid = candidate_grid(marks);
+        let step = match technique.step(&mut grid) {
+            Acted(step) => step,
+            _ => panic!("no {} found", technique.name()),
+        };
+        let placements = step
+            .placements
+            .iter()
+            .map(|(row, col, value)| format!("r{}c{}={}", row + 1, col + 1, value));
+        let eliminations = step
+            .eliminations
+            .iter()
+            .map(|(row, col, value)| format!("r{}c{}<>{}", row + 1, col + 1, value));
+        let mut changes = placements.chain(eliminations).collect::<Vec<_>>();
+        let mut expected = expected.to_vec();
+        changes.sort();
+        expected.sort();
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn x_wing() {
+        let marks = "
+            17 4   3    9  8   67  2    5   167
+            6  789 1789 4  2   5   138  38  178
+            2  578 578  37 36  1   68   9   4
+            9  568 2568 13 135 4   1568 7   1268
+            3  57  257  6  157 8   49   24  129
+            4  1   5678 2  57  9   568  68  3
+            8  2   17   5  16  367 49   346 69
+            17 69  69   17 4   23  38   238 5
+            5  3   4    8  9   26  7    1   26";
+        check_step(BasicFish::new(2), marks, &["r7c6<>6", "r4c9<>6", "r7c9<>6"]);
+    }
+
+    #[test]
+    fn swordfish() {
+        let marks = "
+            359   4   7     2359  8   29    239   6    1
+            1389  6   12389 23479 37  12479 23489 2358 234589
+            189   35  1289  6     35  1249  7     28   2489
+            6     2   489   48    1   3     5     7    489
+            3478  37  348   248   9   5     12348 128  6
+            34589 1   34589 2478  6   2478  23489 238  23489
+            2     8   356   3579  4   679   13    135  357
+            37    9   36    1     357 678   238   4    23578
+            14    357 14    378   2   78    6     9    3578";
+        check_step(BasicFish::new(3), marks, &["r2c9<>5", "r7c9<>5"]);
+    }
+
+    #[test]
+    fn jellyfish() {
+        let marks = "
+            2     4     57     9    8   1    3     57     6
+            159   6     59     23   7   23   15    8      4
+            18    3     78     5    6   4    2     17     9
+            69    79    3679   1    239 5    4     2379   8
+            15689 15789 356789 2348 239 2389 15679 123579 1257
+            4     1589  2      7    39  6    159   1359   15
+            3     589   1      268  259 7    589   4      25
+            7     2     589    38   4   389  1589  6      15
+            5689  589   4      268  1   289  5789  2579   3";
+        check_step(
+            BasicFish::new(4),
+            marks,
+            &["r5c2<>7", "r5c3<>7", "r5c7<>7", "r5c8<>7"],
+        );
+    }
+
+    #[test]
+    fn finned_x_wing() {
+        let marks = "
+            6     4789  2      14789 5    479   1389 179   39
+            1789  5789  15789  1789  169  3     1689 4     2
+            13789 4789  134789 14789 1469 2     1689 15679 569
+            4     3     5679   1579  2    8     169  1569  569
+            789   1     56789  34579 349  45679 2    3569  48
+            289   25689 5689   13459 1349 4569  7    13569 48
+            5     4689  134689 2     7    149   3469 69    369
+            279   24679 4679   3459  349  459   4569 8     1
+            139   49    1349   6     8    1459  3459 2     7";
+        check_step(FinnedFish::finned(2), marks, &["r3c3<>7"]);
+    }
+
+    #[test]
+    fn finned_swordfish() {
+        let marks = "
+            3478  6   3478  5      238  1    478    9     24
+            1     2   78    46     9    46   78     5     3
+            9     5   34    23     38   7    1468   12468 1246
+            2356  4   235   8      1236 9    16     7     126
+            367   39  2379  1234   1346 234  5      1246  8
+            26    8   1     7      246  5    469    3     2469
+            348   139 3489  13469  5    346  2      1468  7
+            23458 139 24589 123469 7    2346 134689 1468  14569
+            2345  7   6     12349  1234 8    1349   14    1459";
+        check_step(FinnedFish::finned(3), marks, &["r5c3<>2"]);
+    }
+
+    #[test]
+    fn finned_jellyfish() {
+        let marks = "
+            179   5      2     39  4     6    8    1379 137
+            14689 134689 389   359 358   7    45   2    145
+            4789  34789  3789  2   358   1    6    3479 3457
+            567   367    4     8   23567 235  9    136  1235
+            2     36789  35789 4   1     359  57   356  35
+            5679  3679   1     37  23567 2359 2457 3456 8
+            4579  2479   6     1   257   245  3    8    2457
+            14578 12478  578   357 9     3458 24   457  6
+            3     2478   578   6   257   2458 1    457  9";
+        check_step(FinnedFish::finned(4), marks, &["r5c9<>5"]);
+    }
+
+    #[test]
+    fn sashimi_x_wing() {
+        let marks = "
+            6    179   8     3     1479  2      5    479   19
+            159  4     13579 579   1679  15679  139  8     2
+            159  2     13579 45789 1479  14589  1349 34679 1369
+            7    159   2     6     1349  134589 1389 39    139
+            8    169   169   279   12379 1379   1369 5     4
+            3    1569  4     589   19    1589   1689 2     7
+            2    8     679   1     5     34679  349  3469  369
+            149  13679 1679  479   8     34679  2    13469 5
+            1459 1369  1569  249   23469 3469   7    13469 8";
+        check_step(FinnedFish::sashimi(2), marks, &["r7c6<>4"]);
+    }
+
+    #[test]
+    fn sashimi_swordfish() {
+        let marks = "
+            3478  6   3478 5      238  1    478    9     24
+            1     2   78   46     9    46   78     5     3
+            9     5   34   23     38   7    1468   12468 1246
+            2356  4   25   8      136  9    16     7     126
+            367   39  379  1234   1346 234  5      1246  8
+            26    8   1    7      246  5    469    3     2469
+            348   139 3489 13469  5    346  2      1468  7
+            23458 139 25   123469 7    2346 134689 1468  14569
+            2345  7   6    12349  1234 8    1349   14    1459";
+        check_step(FinnedFish::sashimi(3), marks, &["r9c5<>2"]);
+    }
+
+    #[test]
+    fn sashimi_jellyfish() {
+        let marks = "
+            2     14579 579    49    8   149   3     157    1567
+            159   6     59     239   7   1239  15    8      4
+            18    3     78     5     6   14    2     17     9
+            69    79    3679   1     239 5     4     2379   8
+            15689 15789 356789 23489 239 23489 15679 123579 12567
+            4     1589  2      7     39  6     159   1359   15
+            3     589   1      2689  259 7     589   4      25
+            7     2     589    389   4   389   1589  6      15
+            5689  589   4      2689  1   289   5789  2579   3";
+        check_step(FinnedFish::sashimi(4), marks, &["r5c4<>3", "r5c6<>3"]);
+    }
+
+    #[test]
+    fn naked_pair() {
+        let marks = "
+            1     378  3678  9     2    345 34568 3568 348
+            5     2    4     368   1    7   368   368  9
+            3689  389  3689  34568 4568 345 2     7    1
+            3679  5    3679  346   469  8   1     36   2
+            36789 3789 36789 1     4569 2   47    3568 47
+            4     1    2     7     56   35  3568  9    38
+            2378  6    3578  2458  458  9   378   1    378
+            278   78   1     28    3    6   9     4    5
+            389   4    3589  58    7    1   38    2    6";
+        check_step(
+            NakedSubset::new(2),
+            marks,
+            &["r5c1<>7", "r5c2<>7", "r5c3<>7", "r5c5<>4"],
+        );
+    }
+
+    #[test]
+    fn naked_triple() {
+        let marks = "
+            6    17    1489   3    189   2     5    479  89
+            489  5     3489   4789 6789  46789 3489 1    2
+            1489 2     134789 5    189   1489  3489 37   6
+            7    4     2      6    1389  5     18   39   389
+            18   169   1689   279  237   1379  369  5    4
+            3    169   5      489  189   1489  1689 2    7
+            2    8     679    1    5     3679  349  3469 39
+            59   13679 1679   789  4     36789 2    3689 15
+            459  1369  1469   289  23689 3689  7    3689 15";
+        check_step(
+            NakedSubset::new(3),
+            marks,
+            &[
+                "r2c5<>8", "r2c5<>9", "r4c5<>1", "r4c5<>8", "r4c5<>9", "r9c5<>8", "r9c5<>9",
+            ],
+        );
+    }
+
+    #[test]
+    fn naked_quad() {
+        let marks = "
+            139   3459 1359 6    7   12 249    8   349
+            13789 3479 6    48   89  12 5      279 3479
+            789   2    79   458  589 3  479    6   1
+            369   1    4    38   368 7  689    5   2
+            679   679  8    1    2   5  3      4   679
+            2     3567 357  9    368 4  1678   17  678
+            13679 3679 2    357  35  89 146789 179 456789
+            5     8    179  27   4   6  1279   3   79
+            4     3679 379  2357 1   89 26789  279 56789";
+        check_step(
+            NakedSubset::new(4),
+            marks,
+            &[
+                "r7c7<>1", "r7c7<>7", "r7c7<>9", "r7c9<>7", "r7c9<>9", "r9c7<>2", "r9c7<>7",
+                "r9c7<>9", "r9c9<>7", "r9c9<>9",
+            ],
+        );
+    }
+
+    #[test]
+    fn hidden_pair() {
+        let marks = "
+            1     378  3678  9     2    345 34568  3568 348
+            5     2    4     368   1    7   368    368  9
+            3689  389  3689  34568 4568 345 2      7    1
+            3679  5    3679  346   469  8   1      36   2
+            36789 3789 36789 1     4569 2   345678 3568 3478
+            4     1    2     7     56   35  3568   9    38
+            2378  6    3578  2458  458  9   378    1    378
+            278   78   1     28    3    6   9      4    5
+            389   4    3589  58    7    1   38     2    6";
+        check_step(
+            HiddenSubset::new(2),
+            marks,
+            &[
+                "r5c7<>3", "r5c7<>5", "r5c7<>6", "r5c7<>8", "r5c9<>3", "r5c9<>8",
+            ],
+        );
+    }
+
+    #[test]
+    fn hidden_triple() {
+        let marks = "
+            256  1267  8     4    257   2569 1269  3    19
+            2456 1267  12467 3    257   2569 12689 1689 189
+            9    236   236   268  28    1    5     7    4
+            7    9     2356  1256 12345 8    136   146  13
+            2368 2368  236   126  1234  7    1369  1469 5
+            1    4     356   9    35    356  78    2    78
+            348  1378  9     1578 6     345  1378  18   2
+            2368 5     12367 1278 1238  23   4     189  13789
+            2348 12378 12347 1278 9     234  1378  5    6";
+        check_step(
+            HiddenSubset::new(3),
+            marks,
+            &[
+                "r2c3<>2", "r2c3<>6", "r8c3<>2", "r8c3<>3", "r8c3<>6", "r9c3<>2", "r9c3<>3",
+            ],
+        );
+    }
+
+    #[test]
+    fn hidden_quad() {
+        let marks = "
+            2  4  57  9   8   1  3    57     6
+            15 6  59  23  7   23 15   8      4
+            18 3  78  5   6   4  2    17     9
+            9  7  36  1   23  5  4    23     8
+            15 15 35  4   239 8  6    123579 1257
+            4  8  2   7   39  6  159  1359   15
+            3  59 1   268 259 7  589  4      25
+            7  2  589 38  4   39 1589 6      15
+            6  59 4   28  1   29 5789 2579   3";
+        check_step(
+            HiddenSubset::new(4),
+            marks,
+            &["r5c3<>5", "r5c8<>1", "r5c8<>5", "r5c9<>1", "r5c9<>5"],
+        );
+    }
+
+    #[test]
+    fn pointing() {
+        let marks = "
+            1    378 3678 9     2    345 34568 3568 348
+            5    2   4    368   1    7   368   368  9
+            3689 389 3689 34568 4568 345 2     7    1
+            3679 5   3679 346   469  8   1     36   2
+            3689 389 3689 1     569  2   47    3568 47
+            4    1   2    7     56   35  3568  9    38
+            2378 6   3578 2458  458  9   378   1    378
+            278  78  1    28    3    6   9     4    5
+            389  4   3589 58    7    1   38    2    6";
+        check_step(Pointing, marks, &["r1c6<>4"]);
+    }
+
+    #[test]
+    fn box_line_reduction() {
+        let marks = "
+            17 4   3    9   8    67   2     5     167
+            6  789 1789 4   2    5    138   38    178
+            2  578 578  37  36   1    68    9     4
+            9  568 2568 13  135  4    1568  7     1268
+            3  57  257  6   157  8    1459  24    129
+            4  1   5678 2   57   9    568   68    3
+            8  2   1679 5   1346 367  3469  346   69
+            17 679 1679 137 1346 2367 34689 23468 5
+            5  3   4    8   9    26   7     1     26";
+        check_step(BoxLineReduction, marks, &["r7c5<>3", "r8c4<>3", "r8c5<>3"]);
+    }
+
+    #[test]
+    fn xy_wing() {
+        let marks = "
+            4   8     7   3  12   12  56 9  56
+            59  359   39  6  48   48  2  7  1
+            1   2     6   57 9    57  3  8  4
+            7   34    5   89 348  489 1  6  2
+            69  13469 349 2  1346 57  8  34 57
+            28  1346  28  57 1346 14  57 34 9
+            58  45    1   48 7    6   9  2  3
+            3   67    89  1  28   289 4  5  67
+            269 4679  249 49 5    3   67 1  8";
+        check_step(XYWing, marks, &["r9c1<>9", "r2c3<>9"]);
+    }
+
+    #[test]
+    fn xyz_wing() {
+        let marks = "
+            3   5    6    18  7  18  2 9   4
+            29  79   279  36  4  36  8 5   1
+            8   4    1    9   5  2   7 3   6
+            26  1    23   4   36 5   9 78  78
+            7   39   4    38  1  389 6 2   5
+            569 89   589  2   69 7   4 1   3
+            1   2    3789 367 39 369 5 4   789
+            459 679  579  17  8  149 3 67  2
+            49  3678 3789 5   2  349 1 678 789";
+        check_step(XYZWing, marks, &["r9c6<>3"]);
+    }
+
+    #[test]
+    fn skyscraper() {
+        let marks = "
+            4   15 35  2  7 39 6  189 58
+            7   9  8   1  5 6  2  3   4
+            16  2  356 8  4 39 15 19  7
+            2   3  7   4  6 8  9  5   1
+            8   4  9   5  3 1  7  2   6
+            5   6  1   7  9 2  8  4   3
+            36  8  2   36 1 5  4  7   9
+            169 7  56  69 2 4  3  18  58
+            39  15 4   39 8 7  15 6   2";
+        check_step(Skyscraper, marks, &["r3c1<>1", "r9c2<>1"]);
+    }
+
+    #[test]
+    fn two_string_kite() {
+        let marks = "
+            59    6    2     3  1     4     8   59  7
+            14589 3    4589  58 6789  5679  2   16  59
+            1589  7    589   2  689   569   4   16  3
+            4578  2458 4578  9  24678 12567 57  3   16
+            6     589  1     58 378   357   579 4   2
+            34579 2459 34579 14 2467  12567 579 8   16
+            2     58   578   6  39    39    1   57  4
+            3479  49   3479  14 5     12    6   279 8
+            459   1    6     7  24    8     3   259 59";
+        check_step(TwoStringKite, marks, &["r6c1<>4"]);
+    }
+
+    #[test]
+    fn turbot_fish() {
+        let marks = "
+            359    4   7      2359   8    29    239    6    1
+            13589  6   123589 234579 3579 12479 23489  2358 234589
+            13589  35  123589 6      359  1249  7      2358 234589
+            6      2   489    489    1    3     5      7    489
+            34789  37  3489   24789  79   5     123489 1238 6
+            345789 1   34589  24789  6    24789 23489  238  23489
+            2      8   356    3579   4    679   13     135  357
+            357    9   356    1      357  678   238    4    23578
+            14     357 14     378    2    78    6      9    3578";
+        check_step(TurbotFish, marks, &["r8c1<>5"]);
+    }
+
+    #[test]
+    fn empty_rectangle() {
+        let marks = "
+            6     489   2     1489  5    47   389  17  39
+            1789  5789  1589  1789  169  3    1689 4   2
+            13789 4789  13489 14789 1469 2    1689 67  5
+            4     3     57    157   2    8    19   156 69
+            89    1     56789 34579 349  5679 2    35  48
+            289   25689 5689  13459 1349 4569 7    135 48
+            5     468   13468 2     7    14   346  9   36
+            27    27    469   3459  349  459  456  8   1
+            139   49    1349  6     8    1459 345  2   7";
+        check_step(EmptyRectangle, marks, &["r6c8<>1"]);
+    }
+
+    #[test]
+    fn w_wing() {
+        let marks = "
+            4  8     7   3  12   12  56 9  56
+            59 59    3   6  48   48  2  7  1
+            1  2     6   57 9    57  3  8  4
+            7  34    5   89 348  489 1  6  2
+            69 13469 49  2  1346 57  8  34 57
+            28 1346  28  57 1346 14  57 34 9
+            58 45    1   48 7    6   9  2  3
+            3  67    89  1  28   289 4  5  67
+            26 4679  249 49 5    3   67 1  8";
+        check_step(WWing, marks, &["r9c3<>4"]);
+    }
+
+    #[test]
+    fn simple_colouring() {
+        let marks = "
+            2     14579 579    49    8   149   3     157    1567
+            159   6     59     239   7   1239  15    8      4
+            18    3     78     5     6   14    2     17     9
+            69    79    3679   1     239 5     4     2379   8
+            15689 15789 356789 23489 239 23489 15679 123579 12567
+            4     1589  2      7     39  6     159   1359   15
+            3     589   1      2689  259 7     589   4      25
+            7     2     589    389   4   389   1589  6      15
+            5689  589   4      2689  1   289   5789  2579   3";
+        check_step(SimpleColouring, marks, &["r5c1<>6", "r5c3<>6"]);
+    }
+
+    #[test]
+    fn multi_colouring() {
+        let marks = "
+            1256 12    1258 1368 7    236  9    4    1236
+            26   7     1248 1368 9    2346 2368 13   5
+            3    1249  1489 168  124  5    28   7    126
+            259  8     7    4    235  2369 1    3569 39
+            4    6     3    59   8    1    27   59   27
+            1259 129   1259 3569 235  7    3456 8    3469
+            8    12349 1246 7    1345 349  456  1569 1469
+            7    1349  146  1359 1345 349  456  2    8
+            19   5     149  2    6    8    347  139  13479";
+        check_step(MultiColouring, marks, &["r7c9<>6"]);
+    }
+
+    #[test]
+    fn x_chain() {
+        let marks = "
+            359    4   7      2359   8    29    239    6    1
+            13589  6   123589 234579 3579 12479 23489  2358 234589
+            13589  35  123589 6      359  1249  7      2358 234589
+            6      2   489    489    1    3     5      7    489
+            34789  37  3489   24789  79   5     123489 128  6
+            345789 1   34589  24789  6    24789 23489  238  23489
+            2      8   356    3579   4    679   13     135  357
+            37     9   356    1      357  678   238    4    23578
+            14     357 14     378    2    78    6      9    3578";
+        check_step(XChain, marks, &["r8c3<>5"]);
+    }
+
+    #[test]
+    fn xy_chain() {
+        let marks = "
+            3     5    6    18   7  18   2 9   4
+            29    79   279  36   4  36   8 5   1
+            8     4    1    9    5  2    7 3   6
+            26    1    23   4    36 5    9 78  78
+            7     39   4    38   1  389  6 2   5
+            569   689  589  2    69 7    4 1   3
+            169   2    3789 1367 39 1369 5 4   789
+            14569 679  579  167  8  1469 3 67  2
+            469   3678 3789 5    2  3469 1 678 789";
+        check_step(XYChain, marks, &["r7c1<>9"]);
+    }
+
+    #[test]
+    fn alternating_inference_chain() {
+        let marks = "
+            6     4789  2      14789 5    479   1389 179   39
+            1789  5789  1589   1789  169  3     1689 4     2
+            13789 4789  13489  14789 1469 2     1689 15679 56
+            4     3     5679   1579  2    8     169  1569  569
+            89    1     56789  34579 349  45679 2    3569  48
+            289   25689 5689   13459 1349 4569  7    13569 48
+            5     4689  134689 2     7    149   3469 69    369
+            27    27    469    3459  349  459   4569 8     1
+            139   49    1349   6     8    1459  3459 2     7";
+        check_step(AlternatingInferenceChain, marks, &["r3c8<>9"]);
+    }
+
+    #[test]
+    fn nice_loop() {
+        let marks = "
+            2     14579 579    49    8   149   3     157    1567
+            159   6     59     239   7   1239  15    8      4
+            18    3     78     5     6   14    2     17     9
+            69    79    3679   1     239 5     4     2379   8
+            15689 15789 356789 23489 239 23489 15679 123579 12567
+            4     1589  2      7     39  6     159   1359   15
+            3     589   1      2689  259 7     589   4      25
+            7     2     589    389   4   389   1589  6      15
+            5689  589   4      2689  1   289   5789  2579   3";
+        check_step(NiceLoop, marks, &["r4c1<>6"]);
+    }
+
+    #[test]
+    fn unique_rectangle_type1() {
+        let marks = "
+            378   6   378 5     2    1    78  9    4
+            1     2   78  46    9    46   78  5    3
+            9     5   4   3     8    7    16  2    16
+            2356  4   25  8     136  9    16  7    126
+            367   39  379 124   1346 234  5   146  8
+            26    8   1   7     46   5    49  3    269
+            348   139 389 1469  5    346  2   1468 7
+            23458 139 25  12469 7    2346 349 1468 1569
+            2345  7   6   1249  134  8    349 14   159";
+        check_step(UniqueRectangle::new(1), marks, &["r1c3<>7", "r1c3<>8"]);
+    }
+
+    #[test]
+    fn unique_rectangle_type2() {
+        let marks = "
+            1    5    2   9 4   6   8 3  7
+            689  689  389 5 38  7   4 2  1
+            478  478  378 2 38  1   6 9  5
+            567  367  4   8 56  35  9 1  2
+            2    89   589 4 1   59  7 6  3
+            69   369  1   7 26  239 5 4  8
+            579  279  6   1 257 25  3 8  4
+            4578 1    578 3 9   48  2 57 6
+            3    2478 578 6 257 48  1 57 9";
+        check_step(
+            UniqueRectangle::new(2),
+            marks,
+            &["r9c2<>8", "r5c3<>8", "r3c3<>8", "r2c3<>8", "r8c1<>8"],
+        );
+    }
+
+    #[test]
+    fn unique_rectangle_type3() {
+        let marks = "
+            139  4    39   5     378  1678 1789  126789 12689
+            8    25   25   47    9    167  147   3      146
+            139  7    6    348   2    18   14589 1489   14589
+            2379 1    4    6     578  2578 389   289    289
+            6    2358 2358 28    14   9    13458 1248   7
+            2579 2589 2589 278   14   3    6     12489  124589
+            239  2389 1    23789 3678 4    789   5      689
+            2459 6    2589 2789  578  2578 14789 14789  3
+            3459 3589 7    1     3568 58   2     4689   4689";
+        check_step(
+            UniqueRectangle::new(3),
+            marks,
+            &[
+                "r4c7<>8", "r4c7<>9", "r5c7<>8", "r6c9<>2", "r6c9<>8", "r6c9<>9",
+            ],
+        );
+    }
+
+    #[test]
+    fn unique_rectangle_type4() {
+        let marks = "
+            6    179   14789  3    1789  2     5    479   89
+            489  5     34789  4789 6789  46789 3489 1     2
+            1489 2     134789 5    1789  1489  3489 34679 3689
+            7    4     2      6    1389  5     1389 39    389
+            189  169   1689   279  12379 1379  1369 5     4
+            3    169   5      489  189   1489  1689 2     7
+            2    8     679    1    5     3679  349  3469  369
+            159  13679 1679   789  4     36789 2    3689  15
+            1459 1369  1469   289  23689 3689  7    3689  15";
+        check_step(UniqueRectangle::new(4), marks, &["r8c1<>1", "r9c1<>1"]);
+    }
+
+    #[test]
+    fn unique_rectangle_type5() {
+        // A grid from newgrids.txt, with wrong possibilities taken out of
+        // r5c4 r5c5 r8c4 r8c5 to leave 349 on one diagonal and 34 on the
+        // other
+        let marks = "
+            6     489   2      1489  5    479   1389 17   39
+            1789  5789  1589   1789  169  3     1689 4    2
+            13789 4789  13489  14789 1469 2     1689 67   5
+            4     3     579    1579  2    8     169  1569 69
+            89    1     56789  349   34   45679 2    359  48
+            289   25689 5689   13459 1349 4569  7    1359 48
+            5     4689  134689 2     7    149   3469 69   369
+            27    27    469    34    349  459   4569 8    1
+            139   49    1349   6     8    1459  3459 2    7";
+        check_step(UniqueRectangle::new(5), marks, &["r6c5<>9"]);
+    }
+
+    #[test]
+    fn unique_rectangle_type6() {
+        let marks = "
+            368 36  68  5  2  1  9    4 7
+            1   4   2   3  7  9  5    8 6
+            9   7   5   46 48 68 3    2 1
+            36  369 4   8  69 2  7    1 5
+            2   5   69  1  46 7  48   3 489
+            7   8   1   49 3  5  24   6 249
+            5   69  689 2  1  4  68   7 3
+            468 1   3   7  5  68 2468 9 248
+            468 2   7   69 89 3  1    5 48";
+        check_step(UniqueRectangle::new(6), marks, &["r1c1<>3", "r4c2<>3"]);
+    }
+
+    #[test]
+    fn hidden_unique_rectangle() {
+        let marks = "
+            69   5    2    4   8    1    79   3679   367
+            4689 346  348  69  7    569  1    24569  24568
+            4689 17   17   3   2    569  4589 4569   4568
+            456  1467 1457 8   1469 2    4579 134579 13457
+            3    124  1458 17  149  47   6    12459  12458
+            2468 9    1478 5   146  3    2478 1247   12478
+            1    248  6    79  3    4789 2457 2457   2457
+            245  234  345  167 14   467  2347 8      9
+            7    348  9    2   5    48   34   16     16";
+        check_step(HiddenUniqueRectangle, marks, &["r7c2<>4"]);
+    }
+
+    #[test]
+    fn bug_plus_one() {
+        let marks = "
+            1   59 24 26 7  8  69 45 3
+            58  6  24 3  49 1  7  58 29
+            89  7  3  29 46 5  26 48 1
+            2   1  5  7  36 34 8  9  46
+            469 49 7  5  8  2  1  3  46
+            46  3  8  69 1  49 5  2  7
+            3   2  1  8  59 6  4  7  59
+            45  45 9  1  2  7  3  6  8
+            7   8  6  4  35 39 29 1  25";
+        check_step(BugPlusOne, marks, &["r5c1=4"]);
+    }
+
+    #[test]
+    fn als_xz() {
+        let marks = "
+            6     489   2      1489  5    479   1389 17   39
+            1789  5789  1589   1789  169  3     1689 4    2
+            13789 4789  13489  14789 1469 2     1689 67   5
+            4     3     579    1579  2    8     169  1569 69
+            89    1     56789  34579 349  45679 2    359  48
+            289   25689 5689   13459 1349 4569  7    1359 48
+            5     4689  134689 2     7    149   3469 69   369
+            27    27    469    3459  349  459   4569 8    1
+            139   49    1349   6     8    1459  3459 2    7";
+        check_step(AlsXZ::singly(), marks, &["r7c8<>6"]);
+    }
+
+    #[test]
+    fn doubly_linked_als_xz() {
+        let marks = "
+            3     8    5     6   247   1    247   2479 479
+            1     67   9     5   2478  248  368   247  368
+            467   2    46    789 3     89   5     1    68
+            4678  4679 12468 237 26789 5    23478 2479 134789
+            59    3    248   278 1     28   2478  6    59
+            678   5679 1268  4   26789 2368 2378  2579 135789
+            29    1    7     239 5     239  46    8    46
+            28    46   3     1   2468  2468 9     57   57
+            45689 59   468   89  46    7    1     3    2";
+        check_step(
+            AlsXZ::doubly(),
+            marks,
+            &[
+                "r4c3<>4", "r9c3<>4", "r4c4<>7", "r3c1<>6", "r5c7<>2", "r5c7<>8",
+            ],
+        );
+    }
+
+    #[test]
+    fn als_xy_wing() {
+        let marks = "
+            6     489   2      1489  5    479   1389 179  39
+            1789  5789  1589   1789  169  3     1689 4    2
+            13789 4789  13489  14789 1469 2     1689 167  5
+            4     3     579    1579  2    8     169  1569 69
+            89    1     56789  34579 349  45679 2    359  48
+            289   25689 5689   13459 1349 4569  7    1359 48
+            5     4689  134689 2     7    149   3469 69   369
+            27    27    469    3459  349  459   4569 8    1
+            139   49    1349   6     8    1459  3459 2    7";
+        check_step(AlsXYWing, marks, &["r1c8<>9"]);
+    }
+
+    #[test]
+    fn death_blossom() {
+        let marks = "
+            59    4     7      36  2   69   156 38   1368
+            8     259   259    4   369 1    56  7    36
+            1     3     6      5   8   7    9   4    2
+            237   1289  123489 367 39  5    124 2368 13689
+            6     29    2349   8   1   239  247 5    379
+            23579 12589 123589 367 4   2369 126 2368 13689
+            259   7     259    1   56  8    3   269  4
+            235   2568  2358   9   356 4    267 1    67
+            4     169   139    2   7   36   8   69   5";
+        check_step(DeathBlossom, marks, &["r9c6<>6"]);
+    }
+
+    #[test]
+    fn sue_de_coq() {
+        let marks = "
+            3     8    5     6     247   1     247   2479 479
+            1     467  9     5     2478  248   368   247  368
+            467   2    46    789   3     489   5     1    68
+            46789 4679 12468 23789 26789 5     23478 2479 134789
+            45789 3    248   2789  1     289   2478  6    45789
+            56789 5679 1268  4     26789 23689 2378  2579 135789
+            29    1    7     239   5     239   46    8    46
+            2468  46   3     1     2468  2468  9     57   57
+            45689 4569 468   89    4689  7     1     3    2";
+        check_step(
+            SueDeCoq,
+            marks,
+            &["r9c5<>8", "r9c5<>9", "r8c1<>4", "r8c1<>6"],
+        );
+    }
+}